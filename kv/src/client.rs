@@ -1,13 +1,9 @@
-mod noise_codec;
-mod pb;
-
 use anyhow::Result;
 use futures::{SinkExt, StreamExt};
+use kv::noise_codec::{NoiseCodec, NoiseStream, NOISE_PARAMS};
+use kv::pb::{Request, Response};
 use tokio::net::TcpStream;
 
-use crate::noise_codec::{NoiseCodec, NoiseStream, NOISE_PARAMS};
-use crate::pb::{Request, Response};
-
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt()
@@ -0,0 +1,177 @@
+use std::collections::{BTreeSet, HashMap};
+use std::str::FromStr;
+
+use anyhow::anyhow;
+
+/// Which key is evicted first once the store is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// least recently used
+    Lru,
+    /// least frequently used, ties are broken by recency
+    Lfu,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lru" => Ok(Self::Lru),
+            "lfu" => Ok(Self::Lfu),
+            _ => Err(anyhow!("Unknown eviction policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Usage {
+    size: usize,
+    hits: u64,
+    last_access: u64,
+}
+
+/// Tracks the memory used by every key and picks the keys to evict when it goes over
+/// `max_memory`. The size of a key is the length of the key plus the length of its value.
+#[derive(Debug)]
+pub(crate) struct Evictor {
+    policy: EvictionPolicy,
+    max_memory: usize,
+    used: usize,
+    // logical clock, bumped on every access
+    clock: u64,
+    usages: HashMap<String, Usage>,
+    // keys ordered from the first to the last candidate for eviction
+    queue: BTreeSet<(u64, u64, String)>,
+}
+
+impl Evictor {
+    pub fn new(max_memory: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            policy,
+            max_memory,
+            used: 0,
+            clock: 0,
+            usages: HashMap::new(),
+            queue: BTreeSet::new(),
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.usages.contains_key(key)
+    }
+
+    /// Whether a key of `size` bytes can be stored at all.
+    pub fn fits(&self, size: usize) -> bool {
+        size <= self.max_memory
+    }
+
+    /// Record a read of `key`.
+    pub fn access(&mut self, key: &str) {
+        if let Some(usage) = self.untrack(key) {
+            self.track(key.to_owned(), usage.size, usage.hits);
+        }
+    }
+
+    /// Record a write of `key` with `size` bytes, returning the keys which must be evicted to
+    /// get back under the limit. The written key itself is never evicted.
+    pub fn insert(&mut self, key: &str, size: usize) -> Vec<String> {
+        let hits = self.untrack(key).map(|usage| usage.hits).unwrap_or(0);
+        self.track(key.to_owned(), size, hits);
+
+        let mut victims = Vec::new();
+        while self.used > self.max_memory {
+            let victim = match self.queue.iter().find(|(_, _, k)| k != key) {
+                Some((_, _, k)) => k.clone(),
+                None => break,
+            };
+            self.untrack(&victim);
+            victims.push(victim);
+        }
+        victims
+    }
+
+    /// Stop tracking `key`, e.g. when it is deleted.
+    pub fn remove(&mut self, key: &str) {
+        self.untrack(key);
+    }
+
+    fn track(&mut self, key: String, size: usize, hits: u64) {
+        self.clock += 1;
+        let usage = Usage {
+            size,
+            hits: hits + 1,
+            last_access: self.clock,
+        };
+        self.used += size;
+        self.queue.insert(self.rank(&key, &usage));
+        self.usages.insert(key, usage);
+    }
+
+    fn untrack(&mut self, key: &str) -> Option<Usage> {
+        let usage = self.usages.remove(key)?;
+        self.queue.remove(&self.rank(key, &usage));
+        self.used -= usage.size;
+        Some(usage)
+    }
+
+    fn rank(&self, key: &str, usage: &Usage) -> (u64, u64, String) {
+        match self.policy {
+            EvictionPolicy::Lru => (usage.last_access, 0, key.to_owned()),
+            EvictionPolicy::Lfu => (usage.hits, usage.last_access, key.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lru_should_evict_least_recently_used() {
+        let mut evictor = Evictor::new(30, EvictionPolicy::Lru);
+        assert!(evictor.insert("a", 10).is_empty());
+        assert!(evictor.insert("b", 10).is_empty());
+        assert!(evictor.insert("c", 10).is_empty());
+
+        evictor.access("a");
+        assert_eq!(evictor.insert("d", 10), vec!["b".to_string()]);
+        assert_eq!(
+            evictor.insert("e", 20),
+            vec!["c".to_string(), "a".to_string()]
+        );
+        assert_eq!(evictor.used(), 30);
+    }
+
+    #[test]
+    fn lfu_should_evict_least_frequently_used() {
+        let mut evictor = Evictor::new(30, EvictionPolicy::Lfu);
+        evictor.insert("a", 10);
+        evictor.insert("b", 10);
+        evictor.insert("c", 10);
+
+        evictor.access("a");
+        evictor.access("a");
+        evictor.access("b");
+        assert_eq!(evictor.insert("d", 10), vec!["c".to_string()]);
+        // "d" has a single hit but is never evicted by its own write
+        assert_eq!(evictor.insert("e", 10), vec!["d".to_string()]);
+    }
+
+    #[test]
+    fn overwrite_and_remove_should_update_usage() {
+        let mut evictor = Evictor::new(30, EvictionPolicy::Lru);
+        evictor.insert("a", 10);
+        evictor.insert("a", 20);
+        assert_eq!(evictor.used(), 20);
+
+        evictor.remove("a");
+        assert_eq!(evictor.used(), 0);
+        assert!(evictor.fits(30));
+        assert!(!evictor.fits(31));
+    }
+}
@@ -0,0 +1,186 @@
+mod eviction;
+pub mod metrics;
+pub mod noise_codec;
+pub mod pb;
+mod state;
+mod watch;
+
+use std::convert::TryInto;
+use std::future::{self, Future};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, Semaphore};
+use tokio::time;
+use tracing::{info, info_span, warn, Instrument};
+
+pub use crate::eviction::EvictionPolicy;
+use crate::noise_codec::{NoiseCodec, NoiseStream, NOISE_PARAMS};
+use crate::pb::request::Command;
+use crate::pb::{Request, RequestUnwatch, RequestWatch, Response};
+pub use crate::state::ServerState;
+use crate::watch::Watcher;
+
+/// Maximum number of concurrent client connections. Once reached, the accept loop waits for a
+/// connection to close before accepting a new one.
+const MAX_CONNECTIONS: usize = 1024;
+
+/// A connection which sends nothing for this long (including during the handshake) is closed,
+/// unless it is watching keys.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Serve kv clients accepted on `listener` forever.
+pub async fn serve(listener: TcpListener, state: Arc<ServerState>) -> Result<()> {
+    serve_with_shutdown(listener, state, future::pending::<()>()).await
+}
+
+/// Accept connections until `shutdown` completes, then wait for every in-flight connection to
+/// finish its current request before returning.
+pub async fn serve_with_shutdown(
+    listener: TcpListener,
+    state: Arc<ServerState>,
+    shutdown: impl Future,
+) -> Result<()> {
+    let limit = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    // dropping the sender tells every connection to stop after its current request
+    let (notify_shutdown, _) = broadcast::channel::<()>(1);
+    // every connection holds a clone of the sender, `recv` returns once all of them are gone
+    let (shutdown_complete_tx, mut shutdown_complete_rx) = mpsc::channel::<()>(1);
+
+    tokio::pin!(shutdown);
+
+    loop {
+        let permit = tokio::select! {
+            permit = limit.clone().acquire_owned() => permit?,
+            _ = &mut shutdown => break,
+        };
+
+        let (stream, addr) = tokio::select! {
+            res = listener.accept() => res?,
+            _ = &mut shutdown => break,
+        };
+        info!("New client: {:?} accepted", addr);
+
+        let shared = state.clone();
+        let mut shutdown_rx = notify_shutdown.subscribe();
+        let shutdown_complete = shutdown_complete_tx.clone();
+
+        let span = info_span!("conn", %addr);
+        tokio::spawn(
+            async move {
+                shared.metrics().active_connections.inc();
+                if let Err(e) = handle_connection(stream, shared.clone(), &mut shutdown_rx).await {
+                    warn!("Client error: {:?}", e);
+                }
+                shared.metrics().active_connections.dec();
+                info!("Client disconnected");
+                drop(permit);
+                drop(shutdown_complete);
+            }
+            .instrument(span),
+        );
+    }
+
+    info!(
+        "Shutting down, draining {} connections",
+        MAX_CONNECTIONS - limit.available_permits()
+    );
+    drop(notify_shutdown);
+    drop(shutdown_complete_tx);
+    let _ = shutdown_complete_rx.recv().await;
+
+    Ok(())
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    state: Arc<ServerState>,
+    shutdown: &mut broadcast::Receiver<()>,
+) -> Result<()> {
+    // let mut stream = LengthDelimitedCodec::builder()
+    //     .length_field_length(2)
+    //     .new_framed(stream);
+
+    let mut stream = NoiseCodec::builder(NOISE_PARAMS, false).new_framed(stream)?;
+    let handshake = time::timeout(IDLE_TIMEOUT, stream.handshake()).await;
+    if !matches!(handshake, Ok(Ok(()))) {
+        state.metrics().handshake_failures.inc();
+    }
+    handshake??;
+
+    let mut watcher = Watcher::default();
+
+    loop {
+        // shutdown is only observed between requests, so a request already read is always
+        // answered before the connection is closed
+        let buf = tokio::select! {
+            res = stream.next() => match res {
+                Some(buf) => buf?,
+                None => break,
+            },
+            event = watcher.next_event() => {
+                stream.send(Response::from(event?).into()).await?;
+                continue;
+            }
+            _ = time::sleep(IDLE_TIMEOUT), if !watcher.is_watching() => {
+                info!("Closing idle connection");
+                break;
+            }
+            _ = shutdown.recv() => break,
+        };
+
+        // a frame which decrypts fine but holds no valid command is answered with a 400, the
+        // connection stays usable
+        let cmd = match TryInto::<Request>::try_into(buf).map(|msg| msg.command) {
+            Ok(Some(cmd)) => cmd,
+            Ok(None) | Err(_) => {
+                warn!("Got an invalid request");
+                state
+                    .metrics()
+                    .request_errors
+                    .with_label_values(&["invalid"])
+                    .inc();
+                stream.send(Response::bad_request().into()).await?;
+                continue;
+            }
+        };
+        let name = cmd.name();
+
+        async {
+            info!("Got a command: {cmd:?}");
+            let timer = state
+                .metrics()
+                .request_duration
+                .with_label_values(&[name])
+                .start_timer();
+
+            let response = match cmd {
+                Command::Watch(RequestWatch { key_or_prefix }) => {
+                    watcher.watch(&state, key_or_prefix)
+                }
+                Command::Unwatch(RequestUnwatch { key_or_prefix }) => {
+                    watcher.unwatch(key_or_prefix)
+                }
+                cmd => state.execute(cmd),
+            };
+            if response.code != 0 {
+                state
+                    .metrics()
+                    .request_errors
+                    .with_label_values(&[name])
+                    .inc();
+            }
+            stream.send(response.into()).await?;
+
+            timer.observe_duration();
+            Ok::<(), anyhow::Error>(())
+        }
+        .instrument(info_span!("request", command = name))
+        .await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use hyper::header::CONTENT_TYPE;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Server};
+use prometheus::{
+    histogram_opts, opts, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge, Registry,
+    TextEncoder,
+};
+
+/// Prometheus metrics of the kv server. Cloning is cheap, every clone updates the same
+/// underlying collectors.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// latency of each request, from the decoded frame to the response being sent
+    pub request_duration: HistogramVec,
+    /// requests answered with a non-zero code, or frames which could not be decoded
+    pub request_errors: IntCounterVec,
+    pub active_connections: IntGauge,
+    pub handshake_failures: IntCounter,
+    /// keys and values held by a bounded store, in bytes
+    pub memory_used: IntGauge,
+    pub evictions: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let request_duration = HistogramVec::new(
+            histogram_opts!(
+                "kv_request_duration_seconds",
+                "Latency of kv requests in seconds"
+            ),
+            &["command"],
+        )
+        .unwrap();
+        let request_errors = IntCounterVec::new(
+            opts!("kv_request_errors_total", "Number of failed kv requests"),
+            &["command"],
+        )
+        .unwrap();
+        let active_connections =
+            IntGauge::new("kv_active_connections", "Number of open client connections").unwrap();
+        let handshake_failures = IntCounter::new(
+            "kv_handshake_failures_total",
+            "Number of failed noise handshakes",
+        )
+        .unwrap();
+        let memory_used = IntGauge::new(
+            "kv_memory_used_bytes",
+            "Size of the keys and values in a bounded store",
+        )
+        .unwrap();
+        let evictions = IntCounter::new(
+            "kv_evictions_total",
+            "Number of keys evicted from the store",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(request_errors.clone())).unwrap();
+        registry
+            .register(Box::new(active_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(handshake_failures.clone()))
+            .unwrap();
+        registry.register(Box::new(memory_used.clone())).unwrap();
+        registry.register(Box::new(evictions.clone())).unwrap();
+
+        Self {
+            registry,
+            request_duration,
+            request_errors,
+            active_connections,
+            handshake_failures,
+            memory_used,
+            evictions,
+        }
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .unwrap();
+        buf
+    }
+
+    /// Serve the metrics over http on `addr`, every path returns the full text exposition.
+    pub async fn serve(self, addr: SocketAddr) -> Result<()> {
+        let make_svc = make_service_fn(move |_| {
+            let metrics = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_req| {
+                    let body = metrics.encode();
+                    async move {
+                        let res = hyper::Response::builder()
+                            .header(CONTENT_TYPE, TextEncoder::new().format_type())
+                            .body(Body::from(body))
+                            .unwrap();
+                        Ok::<_, Infallible>(res)
+                    }
+                }))
+            }
+        });
+
+        Server::bind(&addr).serve(make_svc).await?;
+
+        Ok(())
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
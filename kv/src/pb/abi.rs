@@ -1,6 +1,6 @@
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Request {
-    #[prost(oneof="request::Command", tags="1, 2")]
+    #[prost(oneof="request::Command", tags="1, 2, 3, 4, 5")]
     pub command: ::core::option::Option<request::Command>,
 }
 /// Nested message and enum types in `Request`.
@@ -11,6 +11,12 @@ pub mod request {
         Get(super::RequestGet),
         #[prost(message, tag="2")]
         Put(super::RequestPut),
+        #[prost(message, tag="3")]
+        Delete(super::RequestDelete),
+        #[prost(message, tag="4")]
+        Watch(super::RequestWatch),
+        #[prost(message, tag="5")]
+        Unwatch(super::RequestUnwatch),
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -21,6 +27,9 @@ pub struct Response {
     pub key: ::prost::alloc::string::String,
     #[prost(bytes="vec", tag="3")]
     pub value: ::prost::alloc::vec::Vec<u8>,
+    /// set when the response is a change pushed to a watcher
+    #[prost(message, optional, tag="4")]
+    pub event: ::core::option::Option<WatchEvent>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RequestGet {
@@ -34,3 +43,44 @@ pub struct RequestPut {
     #[prost(bytes="vec", tag="2")]
     pub value: ::prost::alloc::vec::Vec<u8>,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestDelete {
+    #[prost(string, tag="1")]
+    pub key: ::prost::alloc::string::String,
+}
+/// stream a `WatchEvent` for every change of the keys starting with `key_or_prefix`
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestWatch {
+    #[prost(string, tag="1")]
+    pub key_or_prefix: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RequestUnwatch {
+    #[prost(string, tag="1")]
+    pub key_or_prefix: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WatchEvent {
+    #[prost(enumeration="watch_event::Kind", tag="1")]
+    pub kind: i32,
+    #[prost(string, tag="2")]
+    pub key: ::prost::alloc::string::String,
+    /// empty if the key did not exist before the change
+    #[prost(bytes="vec", tag="3")]
+    pub old_value: ::prost::alloc::vec::Vec<u8>,
+    /// empty for a delete
+    #[prost(bytes="vec", tag="4")]
+    pub new_value: ::prost::alloc::vec::Vec<u8>,
+    /// store revision the change was made at, increases by one for every change
+    #[prost(uint64, tag="5")]
+    pub revision: u64,
+}
+/// Nested message and enum types in `WatchEvent`.
+pub mod watch_event {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Kind {
+        Put = 0,
+        Delete = 1,
+    }
+}
@@ -24,6 +24,43 @@ impl Request {
             })),
         }
     }
+
+    pub fn new_delete(key: &str) -> Request {
+        Self {
+            command: Some(Command::Delete(RequestDelete {
+                key: key.to_owned(),
+            })),
+        }
+    }
+
+    pub fn new_watch(key_or_prefix: &str) -> Request {
+        Self {
+            command: Some(Command::Watch(RequestWatch {
+                key_or_prefix: key_or_prefix.to_owned(),
+            })),
+        }
+    }
+
+    pub fn new_unwatch(key_or_prefix: &str) -> Request {
+        Self {
+            command: Some(Command::Unwatch(RequestUnwatch {
+                key_or_prefix: key_or_prefix.to_owned(),
+            })),
+        }
+    }
+}
+
+impl Command {
+    /// Short name of the command, used to label logs and metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Get(_) => "get",
+            Command::Put(_) => "put",
+            Command::Delete(_) => "delete",
+            Command::Watch(_) => "watch",
+            Command::Unwatch(_) => "unwatch",
+        }
+    }
 }
 
 impl Response {
@@ -32,6 +69,7 @@ impl Response {
             code: 0,
             key,
             value,
+            ..Default::default()
         }
     }
 
@@ -42,6 +80,50 @@ impl Response {
             ..Default::default()
         }
     }
+
+    pub fn too_large(key: String) -> Response {
+        Self {
+            code: 413,
+            key,
+            ..Default::default()
+        }
+    }
+
+    pub fn bad_request() -> Response {
+        Self {
+            code: 400,
+            ..Default::default()
+        }
+    }
+}
+
+impl WatchEvent {
+    pub fn new(
+        kind: watch_event::Kind,
+        key: String,
+        old_value: Vec<u8>,
+        new_value: Vec<u8>,
+        revision: u64,
+    ) -> Self {
+        Self {
+            kind: kind as i32,
+            key,
+            old_value,
+            new_value,
+            revision,
+        }
+    }
+}
+
+impl From<WatchEvent> for Response {
+    fn from(event: WatchEvent) -> Self {
+        Self {
+            code: 0,
+            key: event.key.clone(),
+            event: Some(event),
+            ..Default::default()
+        }
+    }
 }
 
 impl TryFrom<BytesMut> for Request {
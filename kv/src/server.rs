@@ -1,38 +1,13 @@
-mod noise_codec;
-mod pb;
-
-use std::convert::TryInto;
 use std::sync::Arc;
 
 use anyhow::Result;
-use dashmap::DashMap;
-use futures::{SinkExt, StreamExt};
-use noise_codec::NoiseStream;
+use kv::{EvictionPolicy, ServerState};
 use tokio::net::TcpListener;
-use tracing::info;
-
-use crate::noise_codec::{NoiseCodec, NOISE_PARAMS};
-use crate::pb::request::Command;
-use crate::pb::{Request, RequestGet, RequestPut, Response};
-
-#[derive(Debug)]
-struct ServerState {
-    store: DashMap<String, Vec<u8>>,
-}
+use tokio::signal;
+use tracing::{info, warn};
 
-impl ServerState {
-    pub fn new() -> Self {
-        ServerState {
-            store: DashMap::new(),
-        }
-    }
-}
-
-impl Default for ServerState {
-    fn default() -> Self {
-        Self::new()
-    }
-}
+/// Address the Prometheus metrics are exported on.
+const METRICS_ADDR: &str = "0.0.0.0:9000";
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -40,44 +15,63 @@ async fn main() -> Result<()> {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
-    let state = Arc::new(ServerState::new());
+    let state = match std::env::var("KV_SNAPSHOT") {
+        Ok(path) => ServerState::with_snapshot(path)?,
+        Err(_) => ServerState::new(),
+    };
+    let state = match std::env::var("KV_MAX_MEMORY") {
+        Ok(max_memory) => {
+            let policy = match std::env::var("KV_EVICTION") {
+                Ok(policy) => policy.parse()?,
+                Err(_) => EvictionPolicy::Lru,
+            };
+            info!(
+                "Store bounded to {} bytes, evicting by {:?}",
+                max_memory, policy
+            );
+            state.with_max_memory(max_memory.parse()?, policy)
+        }
+        Err(_) => state,
+    };
+    let state = Arc::new(state);
+
     let addr = "0.0.0.0:8888";
     let listener = TcpListener::bind(addr).await?;
 
     info!("Listening to {:?}", addr);
 
-    loop {
-        let (stream, addr) = listener.accept().await?;
-        info!("New client: {:?} accepted", addr);
+    let metrics = state.metrics().clone();
+    tokio::spawn(async move {
+        info!("Exporting metrics on {:?}", METRICS_ADDR);
+        if let Err(e) = metrics.serve(METRICS_ADDR.parse().unwrap()).await {
+            warn!("Metrics exporter error: {:?}", e);
+        }
+    });
+
+    kv::serve_with_shutdown(listener, state.clone(), shutdown_signal()).await?;
+    state.flush()?;
 
-        let shared = state.clone();
+    Ok(())
+}
 
-        tokio::spawn(async move {
-            // let mut stream = LengthDelimitedCodec::builder()
-            //     .length_field_length(2)
-            //     .new_framed(stream);
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("failed to listen for ctrl-c");
+    };
 
-            let mut stream = NoiseCodec::builder(NOISE_PARAMS, false).new_framed(stream)?;
-            stream.handshake().await?;
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
 
-            while let Some(Ok(buf)) = stream.next().await {
-                let msg: Request = buf.try_into()?;
-                info!("Got a command: {msg:?}");
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-                let response = match msg.command {
-                    Some(Command::Get(RequestGet { key })) => match shared.store.get(&key) {
-                        Some(v) => Response::new(key, v.value().to_vec()),
-                        None => Response::not_found(key),
-                    },
-                    Some(Command::Put(RequestPut { key, value })) => {
-                        shared.store.insert(key.clone(), value.clone());
-                        Response::new(key, value)
-                    }
-                    None => unimplemented!(),
-                };
-                stream.send(response.into()).await?;
-            }
-            Ok::<(), anyhow::Error>(())
-        });
+    tokio::select! {
+        _ = ctrl_c => info!("Received ctrl-c"),
+        _ = terminate => info!("Received SIGTERM"),
     }
 }
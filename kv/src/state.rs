@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use prost::Message;
+use tokio::sync::broadcast;
+use tracing::info;
+
+use crate::eviction::{EvictionPolicy, Evictor};
+use crate::metrics::Metrics;
+use crate::pb::request::Command;
+use crate::pb::watch_event::Kind;
+use crate::pb::{RequestDelete, RequestGet, RequestPut, Response, WatchEvent};
+
+/// Number of change events buffered for watchers, a watcher falling further behind is dropped.
+const WATCH_CHANNEL_SIZE: usize = 1024;
+
+#[derive(Debug)]
+pub struct ServerState {
+    store: DashMap<String, Vec<u8>>,
+    // where the store is flushed to on shutdown, if any
+    snapshot: Option<PathBuf>,
+    metrics: Metrics,
+    // bumped on every change, carried by the watch events
+    revision: AtomicU64,
+    events: broadcast::Sender<WatchEvent>,
+    // only set when the store is bounded, every write goes through its lock
+    evictor: Option<Mutex<Evictor>>,
+}
+
+impl ServerState {
+    pub fn new() -> Self {
+        Self::with_store(DashMap::new(), None)
+    }
+
+    fn with_store(store: DashMap<String, Vec<u8>>, snapshot: Option<PathBuf>) -> Self {
+        let (events, _) = broadcast::channel(WATCH_CHANNEL_SIZE);
+        ServerState {
+            store,
+            snapshot,
+            metrics: Metrics::new(),
+            revision: AtomicU64::new(0),
+            events,
+            evictor: None,
+        }
+    }
+
+    /// Bound the store to `max_memory` bytes of keys and values, evicting keys chosen by
+    /// `policy` once it is full. Keys already in the store are evicted right away if they don't
+    /// fit.
+    pub fn with_max_memory(mut self, max_memory: usize, policy: EvictionPolicy) -> Self {
+        let mut evictor = Evictor::new(max_memory, policy);
+        for entry in self.store.iter() {
+            evictor.insert(entry.key(), entry.key().len() + entry.value().len());
+        }
+        self.store.retain(|key, _| evictor.contains(key));
+        self.metrics.memory_used.set(evictor.used() as i64);
+
+        self.evictor = Some(Mutex::new(evictor));
+        self
+    }
+
+    /// Create a state backed by a snapshot file, restoring its content if the file exists.
+    pub fn with_snapshot(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let store = DashMap::new();
+
+        if path.exists() {
+            let mut buf = Bytes::from(fs::read(&path)?);
+            while !buf.is_empty() {
+                let RequestPut { key, value } = RequestPut::decode_length_delimited(&mut buf)?;
+                store.insert(key, value);
+            }
+            info!("Restored {} keys from {:?}", store.len(), path);
+        }
+
+        Ok(Self::with_store(store, Some(path)))
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Receive an event for every change made to the store from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<WatchEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn execute(&self, cmd: Command) -> Response {
+        match cmd {
+            Command::Get(RequestGet { key }) => {
+                let value = self.store.get(&key).map(|v| v.value().to_vec());
+                match value {
+                    Some(value) => {
+                        if let Some(evictor) = &self.evictor {
+                            evictor.lock().unwrap().access(&key);
+                        }
+                        Response::new(key, value)
+                    }
+                    None => Response::not_found(key),
+                }
+            }
+            Command::Put(RequestPut { key, value }) => {
+                let size = key.len() + value.len();
+                let mut evictor = self.evictor.as_ref().map(|e| e.lock().unwrap());
+                if matches!(&evictor, Some(evictor) if !evictor.fits(size)) {
+                    return Response::too_large(key);
+                }
+
+                // the entry stays locked until the event is sent, so the events of a key are
+                // always broadcast in revision order
+                let mut entry = self.store.entry(key.clone()).or_default();
+                let old = std::mem::replace(entry.value_mut(), value.clone());
+                self.notify(Kind::Put, key.clone(), old, value.clone());
+                drop(entry);
+
+                if let Some(evictor) = &mut evictor {
+                    for victim in evictor.insert(&key, size) {
+                        self.evict(victim);
+                    }
+                    self.metrics.memory_used.set(evictor.used() as i64);
+                }
+                Response::new(key, value)
+            }
+            Command::Delete(RequestDelete { key }) => {
+                let mut evictor = self.evictor.as_ref().map(|e| e.lock().unwrap());
+                match self.store.entry(key.clone()) {
+                    Entry::Occupied(e) => {
+                        self.notify(Kind::Delete, key.clone(), e.get().clone(), vec![]);
+                        let old = e.remove();
+                        if let Some(evictor) = &mut evictor {
+                            evictor.remove(&key);
+                            self.metrics.memory_used.set(evictor.used() as i64);
+                        }
+                        Response::new(key, old)
+                    }
+                    Entry::Vacant(_) => Response::not_found(key),
+                }
+            }
+            // watches belong to a connection, they are handled before reaching the store
+            Command::Watch(_) | Command::Unwatch(_) => Response::bad_request(),
+        }
+    }
+
+    // must be called with the evictor locked
+    fn evict(&self, key: String) {
+        if let Some((key, old)) = self.store.remove(&key) {
+            info!("Evicted {:?}", key);
+            self.metrics.evictions.inc();
+            self.notify(Kind::Delete, key, old, vec![]);
+        }
+    }
+
+    fn notify(&self, kind: Kind, key: String, old_value: Vec<u8>, new_value: Vec<u8>) {
+        let revision = self.revision.fetch_add(1, Ordering::SeqCst) + 1;
+        // an error only means nobody is watching
+        let _ = self
+            .events
+            .send(WatchEvent::new(kind, key, old_value, new_value, revision));
+    }
+
+    /// Write the whole store to the snapshot file. The file is replaced atomically so a crash
+    /// while flushing never leaves a truncated snapshot behind.
+    pub fn flush(&self) -> Result<()> {
+        let path = match &self.snapshot {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let mut buf = BytesMut::new();
+        for entry in self.store.iter() {
+            let put = RequestPut {
+                key: entry.key().clone(),
+                value: entry.value().clone(),
+            };
+            put.encode_length_delimited(&mut buf)?;
+        }
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, &buf)?;
+        fs::rename(&tmp, path)?;
+        info!("Flushed {} keys to {:?}", self.store.len(), path);
+
+        Ok(())
+    }
+}
+
+impl Default for ServerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
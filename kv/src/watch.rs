@@ -0,0 +1,67 @@
+use anyhow::{anyhow, Result};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::pb::{Response, WatchEvent};
+use crate::ServerState;
+
+/// The watches of a single connection. Every change of the store is broadcast to all
+/// connections, the watcher only lets through the events matching one of its prefixes.
+#[derive(Debug, Default)]
+pub(crate) struct Watcher {
+    prefixes: Vec<String>,
+    // only subscribed while at least one prefix is watched, so connections which never watch
+    // don't slow down the broadcast
+    events: Option<broadcast::Receiver<WatchEvent>>,
+}
+
+impl Watcher {
+    pub fn is_watching(&self) -> bool {
+        !self.prefixes.is_empty()
+    }
+
+    pub fn watch(&mut self, state: &ServerState, key_or_prefix: String) -> Response {
+        if self.events.is_none() {
+            self.events = Some(state.subscribe());
+        }
+        if !self.prefixes.contains(&key_or_prefix) {
+            self.prefixes.push(key_or_prefix.clone());
+        }
+        Response::new(key_or_prefix, vec![])
+    }
+
+    pub fn unwatch(&mut self, key_or_prefix: String) -> Response {
+        let len = self.prefixes.len();
+        self.prefixes.retain(|p| p != &key_or_prefix);
+        if self.prefixes.is_empty() {
+            self.events = None;
+        }
+
+        match self.prefixes.len() < len {
+            true => Response::new(key_or_prefix, vec![]),
+            false => Response::not_found(key_or_prefix),
+        }
+    }
+
+    /// Wait for the next event matching a watched prefix. Never resolves when nothing is
+    /// watched.
+    pub async fn next_event(&mut self) -> Result<WatchEvent> {
+        let events = match &mut self.events {
+            Some(events) => events,
+            None => return futures::future::pending().await,
+        };
+
+        loop {
+            match events.recv().await {
+                Ok(event) if self.prefixes.iter().any(|p| event.key.starts_with(p)) => {
+                    return Ok(event)
+                }
+                Ok(_) => continue,
+                Err(RecvError::Lagged(n)) => {
+                    return Err(anyhow!("Watcher lagged behind by {} events", n))
+                }
+                Err(RecvError::Closed) => return futures::future::pending().await,
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use kv::noise_codec::{NoiseCodec, NoiseStream, NOISE_PARAMS};
+use kv::pb::watch_event::Kind;
+use kv::pb::{Request, Response, WatchEvent};
+use kv::{EvictionPolicy, ServerState};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::codec::Framed;
+
+type ClientStream = Framed<TcpStream, NoiseCodec>;
+
+async fn start_server() -> Result<(SocketAddr, Arc<ServerState>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let state = Arc::new(ServerState::new());
+    tokio::spawn(kv::serve(listener, state.clone()));
+    Ok((addr, state))
+}
+
+// a server stopped by sending on the returned channel
+async fn start_server_with_shutdown(
+    state: Arc<ServerState>,
+) -> Result<(SocketAddr, oneshot::Sender<()>, JoinHandle<Result<()>>)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let (tx, rx) = oneshot::channel();
+    let server = tokio::spawn(kv::serve_with_shutdown(listener, state, rx));
+    Ok((addr, tx, server))
+}
+
+async fn connect(addr: SocketAddr) -> Result<ClientStream> {
+    let stream = TcpStream::connect(addr).await?;
+    let mut stream = NoiseCodec::builder(NOISE_PARAMS, true).new_framed(stream)?;
+    stream.handshake().await?;
+    Ok(stream)
+}
+
+async fn call(stream: &mut ClientStream, msg: impl Into<Bytes>) -> Result<Response> {
+    stream.send(msg.into()).await?;
+    let buf = stream.next().await.unwrap()?;
+    Ok(Response::try_from(buf)?)
+}
+
+#[tokio::test]
+async fn handshake_should_work() -> Result<()> {
+    let (addr, state) = start_server().await?;
+    connect(addr).await?;
+    assert_eq!(state.metrics().handshake_failures.get(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn put_then_get_should_work() -> Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut stream = connect(addr).await?;
+
+    let res = call(&mut stream, Request::new_put("hello", b"world")).await?;
+    assert_eq!(res, Response::new("hello".into(), b"world".to_vec()));
+
+    let res = call(&mut stream, Request::new_get("hello")).await?;
+    assert_eq!(res, Response::new("hello".into(), b"world".to_vec()));
+
+    // another connection sees the same store
+    let mut stream = connect(addr).await?;
+    let res = call(&mut stream, Request::new_get("hello")).await?;
+    assert_eq!(res.value, b"world");
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_missing_key_should_return_not_found() -> Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut stream = connect(addr).await?;
+
+    let res = call(&mut stream, Request::new_get("missing")).await?;
+    assert_eq!(res, Response::not_found("missing".into()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn invalid_request_should_return_bad_request() -> Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut stream = connect(addr).await?;
+
+    // not a protobuf message
+    let res = call(&mut stream, Bytes::from_static(&[0xff, 0xff, 0xff])).await?;
+    assert_eq!(res.code, 400);
+
+    // a request without command
+    let res = call(&mut stream, Request::default()).await?;
+    assert_eq!(res.code, 400);
+
+    // the connection is still usable
+    let res = call(&mut stream, Request::new_put("k", b"v")).await?;
+    assert_eq!(res.code, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn malformed_frame_should_close_connection() -> Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut stream = connect(addr).await?;
+
+    // a frame which is not encrypted with the session keys
+    stream.get_mut().write_all(&[0, 4, 1, 2, 3, 4]).await?;
+    assert!(matches!(stream.next().await, None | Some(Err(_))));
+
+    // the server keeps serving other clients
+    let mut stream = connect(addr).await?;
+    let res = call(&mut stream, Request::new_get("missing")).await?;
+    assert_eq!(res.code, 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn malformed_handshake_should_be_counted() -> Result<()> {
+    let (addr, state) = start_server().await?;
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(&[0, 4, 1, 2, 3, 4]).await?;
+    let mut stream = NoiseCodec::builder(NOISE_PARAMS, true).new_framed(stream)?;
+    assert!(matches!(stream.next().await, None | Some(Err(_))));
+
+    assert_eq!(state.metrics().handshake_failures.get(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_should_stream_changes() -> Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut watcher = connect(addr).await?;
+    let mut stream = connect(addr).await?;
+
+    let res = call(&mut watcher, Request::new_watch("config/")).await?;
+    assert_eq!(res.code, 0);
+
+    call(&mut stream, Request::new_put("config/a", b"1")).await?;
+    call(&mut stream, Request::new_put("other", b"x")).await?;
+    call(&mut stream, Request::new_put("config/a", b"2")).await?;
+    call(&mut stream, Request::new_delete("config/a")).await?;
+
+    let mut events = Vec::new();
+    for _ in 0..3 {
+        let buf = watcher.next().await.unwrap()?;
+        events.push(Response::try_from(buf)?.event.unwrap());
+    }
+    assert_eq!(
+        events,
+        vec![
+            WatchEvent::new(Kind::Put, "config/a".into(), vec![], b"1".to_vec(), 1),
+            WatchEvent::new(
+                Kind::Put,
+                "config/a".into(),
+                b"1".to_vec(),
+                b"2".to_vec(),
+                3
+            ),
+            WatchEvent::new(Kind::Delete, "config/a".into(), b"2".to_vec(), vec![], 4),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn unwatch_should_stop_streaming() -> Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut watcher = connect(addr).await?;
+
+    call(&mut watcher, Request::new_watch("a")).await?;
+    let res = call(&mut watcher, Request::new_unwatch("a")).await?;
+    assert_eq!(res.code, 0);
+    let res = call(&mut watcher, Request::new_unwatch("a")).await?;
+    assert_eq!(res.code, 404);
+
+    // only the response of the put itself comes back, no event
+    let res = call(&mut watcher, Request::new_put("a", b"1")).await?;
+    assert_eq!(res, Response::new("a".into(), b"1".to_vec()));
+    let res = call(&mut watcher, Request::new_get("a")).await?;
+    assert_eq!(res, Response::new("a".into(), b"1".to_vec()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn bounded_store_should_evict_keys() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    // room for two keys of 1 byte with 9 bytes values
+    let state = Arc::new(ServerState::new().with_max_memory(20, EvictionPolicy::Lru));
+    tokio::spawn(kv::serve(listener, state.clone()));
+    let mut stream = connect(addr).await?;
+
+    call(&mut stream, Request::new_put("a", b"123456789")).await?;
+    call(&mut stream, Request::new_put("b", b"123456789")).await?;
+    call(&mut stream, Request::new_get("a")).await?;
+    call(&mut stream, Request::new_put("c", b"123456789")).await?;
+
+    assert_eq!(call(&mut stream, Request::new_get("b")).await?.code, 404);
+    assert_eq!(call(&mut stream, Request::new_get("a")).await?.code, 0);
+    assert_eq!(call(&mut stream, Request::new_get("c")).await?.code, 0);
+    assert_eq!(state.metrics().evictions.get(), 1);
+    assert_eq!(state.metrics().memory_used.get(), 20);
+
+    // a value which can never fit is rejected
+    let res = call(&mut stream, Request::new_put("d", &[0; 20])).await?;
+    assert_eq!(res.code, 413);
+    Ok(())
+}
+
+#[tokio::test]
+async fn shutdown_should_drain_connections() -> Result<()> {
+    let (addr, shutdown, server) = start_server_with_shutdown(Arc::new(ServerState::new())).await?;
+    let mut stream = connect(addr).await?;
+    // watchers are never idle, they are closed by the shutdown too
+    let mut watcher = connect(addr).await?;
+    call(&mut watcher, Request::new_watch("a")).await?;
+    call(&mut stream, Request::new_put("a", b"1")).await?;
+    watcher.next().await.unwrap()?;
+
+    shutdown.send(()).unwrap();
+    for stream in [&mut stream, &mut watcher] {
+        let end = time::timeout(Duration::from_secs(5), stream.next()).await?;
+        assert!(matches!(end, None | Some(Err(_))));
+    }
+    time::timeout(Duration::from_secs(5), server).await???;
+
+    // the listener is closed once the server returns
+    assert!(TcpStream::connect(addr).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn snapshot_should_survive_a_restart() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("kv.db");
+
+    // no snapshot yet, the store starts empty
+    let state = Arc::new(ServerState::with_snapshot(&path)?);
+    let (addr, shutdown, server) = start_server_with_shutdown(state.clone()).await?;
+    let mut stream = connect(addr).await?;
+    assert_eq!(call(&mut stream, Request::new_get("a")).await?.code, 404);
+    call(&mut stream, Request::new_put("a", b"1")).await?;
+    call(&mut stream, Request::new_put("b", b"2")).await?;
+    call(&mut stream, Request::new_delete("b")).await?;
+    call(&mut stream, Request::new_put("c", b"")).await?;
+
+    // what the server binary does on shutdown
+    shutdown.send(()).unwrap();
+    server.await??;
+    state.flush()?;
+    assert!(!path.with_extension("tmp").exists());
+
+    let state = Arc::new(ServerState::with_snapshot(&path)?);
+    let (addr, _shutdown, _) = start_server_with_shutdown(state).await?;
+    let mut stream = connect(addr).await?;
+    let res = call(&mut stream, Request::new_get("a")).await?;
+    assert_eq!(res, Response::new("a".into(), b"1".to_vec()));
+    assert_eq!(call(&mut stream, Request::new_get("b")).await?.code, 404);
+    let res = call(&mut stream, Request::new_get("c")).await?;
+    assert_eq!(res, Response::new("c".into(), vec![]));
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use std::sync::atomic::AtomicBool;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pow::pb::{Block, HashAlgorithm};
+use pow::{pow_v1, pow_v2, pow_v3};
+
+// Every version mines the same blocks. The throughput is counted as the hashes the sequential
+// search needs to find the nonce, the parallel searches may hash a few more before stopping.
+fn mining(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mining");
+    group.sample_size(10);
+
+    let blocks = [
+        ("blake3", HashAlgorithm::Blake3, 16),
+        ("sha256d", HashAlgorithm::Sha256d, 16),
+        ("argon2id", HashAlgorithm::Argon2id, 4),
+        ("scrypt", HashAlgorithm::Scrypt, 4),
+    ];
+    for (name, algorithm, difficulty) in blocks {
+        let block = Block {
+            data: b"hello world".to_vec(),
+            difficulty,
+            algorithm: algorithm as i32,
+            ..Default::default()
+        };
+        let hashes = pow_v1(block.clone()).unwrap().nonce + 1;
+        group.throughput(Throughput::Elements(hashes));
+
+        group.bench_with_input(BenchmarkId::new("pow_v1", name), &block, |b, block| {
+            b.iter(|| pow_v1(block.clone()))
+        });
+        group.bench_with_input(BenchmarkId::new("pow_v2", name), &block, |b, block| {
+            b.iter(|| pow_v2(block.clone(), &AtomicBool::new(false)))
+        });
+        group.bench_with_input(BenchmarkId::new("pow_v3", name), &block, |b, block| {
+            b.iter(|| pow_v3(block.clone(), &AtomicBool::new(false)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, mining);
+criterion_main!(benches);
@@ -1,6 +1,6 @@
 fn main() {
     tonic_build::configure()
         .out_dir("src/pb")
-        .compile(&["abi.proto"], &["."])
+        .compile(&["abi.proto", "health.proto"], &["."])
         .unwrap()
 }
@@ -0,0 +1,241 @@
+//! Bearer token authentication of the tenants of the service, and their quotas.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Interceptor, Request, Status};
+
+use crate::pb::pow_builder_client::PowBuilderClient;
+
+const AUTHORIZATION: &str = "authorization";
+const BEARER: &str = "Bearer ";
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// A client of the service, identified by its token. Its blocks and subscriptions must use its
+/// name as the client name.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tenant {
+    pub name: String,
+    pub token: String,
+    /// Blocks submitted per minute, in bursts of up to as many blocks. Unlimited if not set.
+    #[serde(default)]
+    pub jobs_per_minute: Option<u32>,
+    /// Highest difficulty of the blocks submitted. Unlimited if not set.
+    #[serde(default)]
+    pub max_difficulty: Option<u32>,
+    /// Whether it runs mining workers, which search nonces for the blocks of every tenant.
+    #[serde(default)]
+    pub worker: bool,
+}
+
+// jobs left to submit, refilled continuously up to `jobs_per_minute`
+#[derive(Debug)]
+struct Bucket {
+    jobs: f64,
+    updated: Instant,
+}
+
+#[derive(Debug)]
+struct Account {
+    tenant: Tenant,
+    bucket: Mutex<Bucket>,
+}
+
+/// The tenants, by the SHA-256 hash of their token, so that looking them up doesn't leak the
+/// tokens through timing.
+#[derive(Debug, Default)]
+pub struct Auth {
+    accounts: HashMap<Vec<u8>, Account>,
+}
+
+impl Auth {
+    pub fn new(tenants: Vec<Tenant>) -> Self {
+        let now = Instant::now();
+        let accounts = tenants
+            .into_iter()
+            .map(|tenant| {
+                let bucket = Bucket {
+                    jobs: tenant.jobs_per_minute.unwrap_or_default() as f64,
+                    updated: now,
+                };
+                let account = Account {
+                    tenant,
+                    bucket: Mutex::new(bucket),
+                };
+                (digest(&account.tenant.token), account)
+            })
+            .collect();
+        Self { accounts }
+    }
+
+    /// Read the tenants from a JSON array.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let tenants = serde_json::from_slice(&std::fs::read(path)?)?;
+        Ok(Self::new(tenants))
+    }
+
+    /// Reject the requests without the token of a tenant before their message is even decoded.
+    #[allow(clippy::result_large_err)]
+    pub fn interceptor(self: &Arc<Self>) -> Interceptor {
+        let auth = self.clone();
+        Interceptor::new(move |request: Request<()>| {
+            auth.authenticate(request.metadata())?;
+            Ok(request)
+        })
+    }
+
+    /// The tenant the `authorization` metadata has the token of.
+    #[allow(clippy::result_large_err)]
+    pub fn authenticate(&self, metadata: &MetadataMap) -> Result<&Tenant, Status> {
+        let token = metadata
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix(BEARER))
+            .ok_or_else(|| Status::unauthenticated("Bearer token is required"))?;
+        self.tenant(token)
+            .ok_or_else(|| Status::unauthenticated("Invalid token"))
+    }
+
+    /// The tenant `token` belongs to.
+    pub fn tenant(&self, token: &str) -> Option<&Tenant> {
+        self.accounts
+            .get(&digest(token))
+            .map(|account| &account.tenant)
+    }
+
+    /// Count a block of `difficulty` against the quotas of the tenant, see `refund` if it is not
+    /// mined after all.
+    #[allow(clippy::result_large_err)]
+    pub fn submit(&self, tenant: &Tenant, difficulty: u32) -> Result<(), Status> {
+        if matches!(tenant.max_difficulty, Some(max) if difficulty > max) {
+            return Err(Status::permission_denied(format!(
+                "Difficulty of {} is at most {} bits",
+                tenant.name,
+                tenant.max_difficulty.unwrap()
+            )));
+        }
+        let per_minute = match tenant.jobs_per_minute {
+            Some(per_minute) => per_minute as f64,
+            None => return Ok(()),
+        };
+
+        let account = &self.accounts[&digest(&tenant.token)];
+        let mut bucket = account.bucket.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.updated).as_secs_f64() / MINUTE.as_secs_f64();
+        bucket.jobs = (bucket.jobs + refill * per_minute).min(per_minute);
+        bucket.updated = now;
+        if bucket.jobs < 1.0 {
+            return Err(Status::resource_exhausted(format!(
+                "{} can submit {} blocks per minute",
+                tenant.name, per_minute
+            )));
+        }
+        bucket.jobs -= 1.0;
+        Ok(())
+    }
+
+    /// Give back to the tenant a block counted by `submit` which was then rejected.
+    pub fn refund(&self, tenant: &Tenant) {
+        if let Some(per_minute) = tenant.jobs_per_minute {
+            let account = &self.accounts[&digest(&tenant.token)];
+            let mut bucket = account.bucket.lock().unwrap();
+            bucket.jobs = (bucket.jobs + 1.0).min(per_minute as f64);
+        }
+    }
+}
+
+/// Client side interceptor sending `token`.
+#[allow(clippy::result_large_err)]
+pub fn bearer(token: &str) -> Result<Interceptor> {
+    let value = MetadataValue::from_str(&format!("{}{}", BEARER, token))?;
+    Ok(Interceptor::new(move |mut request: Request<()>| {
+        request.metadata_mut().insert(AUTHORIZATION, value.clone());
+        Ok(request)
+    }))
+}
+
+/// Connect to the service at `addr`, with `token` if the service authenticates its clients.
+pub async fn connect(addr: String, token: Option<&str>) -> Result<PowBuilderClient<Channel>> {
+    let channel = Endpoint::from_shared(addr)?.connect().await?;
+    Ok(match token {
+        Some(token) => PowBuilderClient::with_interceptor(channel, bearer(token)?),
+        None => PowBuilderClient::new(channel),
+    })
+}
+
+fn digest(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(jobs_per_minute: Option<u32>, max_difficulty: Option<u32>) -> Tenant {
+        Tenant {
+            name: "alice".to_string(),
+            token: "secret".to_string(),
+            jobs_per_minute,
+            max_difficulty,
+            worker: false,
+        }
+    }
+
+    fn metadata(value: &str) -> MetadataMap {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(AUTHORIZATION, value.parse().unwrap());
+        metadata
+    }
+
+    #[test]
+    fn tenants_should_be_found_by_token() {
+        let auth = Auth::new(vec![tenant(None, None)]);
+        let alice = auth.authenticate(&metadata("Bearer secret")).unwrap();
+        assert_eq!(alice.name, "alice");
+
+        for value in ["Bearer wrong", "secret", "Basic secret"] {
+            let err = auth.authenticate(&metadata(value)).unwrap_err();
+            assert_eq!(err.code(), tonic::Code::Unauthenticated);
+        }
+        let err = auth.authenticate(&MetadataMap::new()).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn quotas_should_be_enforced() {
+        let auth = Auth::new(vec![tenant(Some(2), Some(20))]);
+        let alice = auth.authenticate(&metadata("Bearer secret")).unwrap();
+
+        let err = auth.submit(alice, 21).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        // the rejected block didn't count
+        auth.submit(alice, 20).unwrap();
+        auth.submit(alice, 20).unwrap();
+        let err = auth.submit(alice, 20).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+
+        // a job every 30 seconds is available again
+        let account = &auth.accounts[&digest("secret")];
+        account.bucket.lock().unwrap().updated -= Duration::from_secs(30);
+        auth.submit(alice, 20).unwrap();
+        assert!(auth.submit(alice, 20).is_err());
+
+        // a rejected block is given back, up to the quota
+        auth.refund(alice);
+        auth.submit(alice, 20).unwrap();
+        auth.refund(alice);
+        auth.refund(alice);
+        auth.refund(alice);
+        auth.submit(alice, 20).unwrap();
+        auth.submit(alice, 20).unwrap();
+        assert!(auth.submit(alice, 20).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::builder::RangedU64ValueParser;
+use clap::{value_parser, Parser};
+use pow::auth::Auth;
+use pow::pow::{DEFAULT_DIFFICULTY, MAX_DIFFICULTY};
+use pow::server::{Config, PowServer};
+use pow::service::{CHANNEL_SIZE, HEARTBEAT_INTERVAL};
+use pow::stratum::{self, Pool};
+use tokio::net::TcpListener;
+use tokio::signal;
+use tokio_stream::wrappers::TcpListenerStream;
+
+/// PoW server: mines the blocks submitted over gRPC, on its own or with remote workers, and
+/// optionally runs a Stratum-like pool on the same chain.
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct Args {
+    /// Address of the gRPC services
+    #[clap(long, env = "POW_ADDR", default_value = "0.0.0.0:8888")]
+    addr: SocketAddr,
+    /// Address of the Stratum pool, e.g. 0.0.0.0:3333, no pool if not set
+    #[clap(long, env = "POW_STRATUM_ADDR")]
+    stratum_addr: Option<SocketAddr>,
+    /// Threads searching nonces, shared by all the jobs, 0 for one per core
+    #[clap(long, env = "POW_THREADS", default_value_t = 0)]
+    threads: usize,
+    /// Number of blocks mined at the same time
+    #[clap(long, env = "POW_CONCURRENT_JOBS", default_value_t = 2, value_parser = at_least_one())]
+    concurrent_jobs: usize,
+    /// Number of blocks waiting to be mined before submissions are rejected
+    #[clap(long, env = "POW_QUEUE_SIZE", default_value_t = 64)]
+    queue_size: usize,
+    /// Leading zero bits of the blocks submitted without a difficulty, and of the pool blocks
+    #[clap(long, env = "POW_DIFFICULTY", default_value_t = DEFAULT_DIFFICULTY)]
+    difficulty: u32,
+    /// Leading zero bits of a pool share, defaults to 256 shares per block on average
+    #[clap(long, env = "POW_SHARE_DIFFICULTY")]
+    share_difficulty: Option<u32>,
+    /// Messages buffered by every subscriber and worker stream
+    #[clap(
+        long,
+        env = "POW_CHANNEL_SIZE",
+        default_value_t = CHANNEL_SIZE,
+        value_parser = at_least_one(),
+    )]
+    channel_size: usize,
+    /// Events buffered between the PoW engine and the gRPC service
+    #[clap(
+        long,
+        env = "POW_EVENT_CHANNEL_SIZE",
+        default_value_t = CHANNEL_SIZE,
+        value_parser = at_least_one(),
+    )]
+    event_channel_size: usize,
+    /// Seconds the running jobs are given to finish on shutdown before being cancelled
+    #[clap(long, env = "POW_GRACE_PERIOD", default_value_t = 30)]
+    grace_period: u64,
+    /// JSON array of the tenants allowed in, with their token and quotas, anyone if not set
+    #[clap(long, env = "POW_TENANTS")]
+    tenants: Option<PathBuf>,
+    /// Directory of the job and result store
+    #[clap(long, env = "POW_STORE", default_value = "pow.db")]
+    store: PathBuf,
+    /// Seconds between the heartbeats of the subscribers
+    #[clap(
+        long,
+        env = "POW_HEARTBEAT",
+        default_value_t = HEARTBEAT_INTERVAL.as_secs(),
+        value_parser = value_parser!(u64).range(1..),
+    )]
+    heartbeat: u64,
+}
+
+// channels and the number of jobs mined at the same time can't be empty
+fn at_least_one() -> RangedU64ValueParser<usize> {
+    RangedU64ValueParser::new().range(1..)
+}
+
+async fn start_server(args: Args) -> Result<()> {
+    if args.difficulty > MAX_DIFFICULTY {
+        anyhow::bail!("Difficulty must be at most {} bits", MAX_DIFFICULTY);
+    }
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()?;
+
+    let auth = args.tenants.map(Auth::load).transpose()?.map(Arc::new);
+    let server = PowServer::new(&Config {
+        concurrent_jobs: args.concurrent_jobs,
+        queue_size: args.queue_size,
+        difficulty: args.difficulty,
+        channel_size: args.channel_size,
+        event_channel_size: args.event_channel_size,
+        heartbeat: Duration::from_secs(args.heartbeat),
+        grace_period: Duration::from_secs(args.grace_period),
+        store: Some(args.store),
+        auth: auth.clone(),
+    })?;
+
+    // miners pool their work on the same chain, as the same tenants
+    if let Some(addr) = args.stratum_addr {
+        let share_difficulty = args
+            .share_difficulty
+            .unwrap_or_else(|| args.difficulty.saturating_sub(8));
+        let mut pool = Pool::new(server.chain(), share_difficulty, args.difficulty);
+        if let Some(auth) = auth {
+            pool = pool.with_auth(auth);
+        }
+        let listener = TcpListener::bind(addr).await?;
+        println!("Stratum listening on {:?}", addr);
+        tokio::spawn(stratum::serve(listener, Arc::new(pool)));
+    }
+
+    let listener = TcpListener::bind(args.addr).await?;
+    println!("Listening on {:?}", args.addr);
+    server
+        .serve(TcpListenerStream::new(listener), shutdown_signal())
+        .await?;
+
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c().await.expect("failed to listen for ctrl-c");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => println!("Received ctrl-c, shutting down"),
+        _ = terminate => println!("Received SIGTERM, shutting down"),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    start_server(Args::parse()).await?;
+
+    Ok(())
+}
@@ -0,0 +1,19 @@
+use anyhow::Result;
+use pow::auth::connect;
+use pow::worker::work;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let addr = "http://localhost:8888";
+    // needed when the server has tenants
+    let token = std::env::var("POW_TOKEN").ok();
+    let name = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "worker1".to_string());
+    let client = connect(addr.to_string(), token.as_deref()).await?;
+
+    println!("Working for {} as {}", addr, name);
+    work(client, name).await?;
+
+    Ok(())
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use tonic::Status;
+
+use crate::challenge::now;
+use crate::pb::Block;
+use crate::pow::{difficulty, pow_hash, verify};
+
+struct Entry {
+    block: Block,
+    // work of the block and all its ancestors
+    work: u128,
+}
+
+#[derive(Default)]
+struct State {
+    // every valid block, by PoW hash
+    blocks: HashMap<Vec<u8>, Entry>,
+    // hash of the last block of the chain with the most work
+    tip: Option<Vec<u8>>,
+}
+
+/// Mined blocks linked by their previous hash. Forks are kept, the best chain is the one with
+/// the most cumulative work, the first one seen winning ties.
+#[derive(Default)]
+pub struct Chain {
+    state: RwLock<State>,
+}
+
+impl Chain {
+    /// Set the height, previous hash and timestamp of a block to mine it on top of the best
+    /// chain.
+    pub fn link(&self, block: Block) -> Block {
+        let timestamp = now();
+        let state = self.state.read().unwrap();
+        match state.tip.as_ref().map(|tip| &state.blocks[tip].block) {
+            Some(tip) => Block {
+                height: tip.height + 1,
+                prev_hash: tip.hash.clone(),
+                timestamp: timestamp.max(tip.timestamp),
+                ..block
+            },
+            None => Block {
+                height: 0,
+                prev_hash: vec![],
+                timestamp,
+                ..block
+            },
+        }
+    }
+
+    /// Validate a mined block against its parent and add it to the chain. Returns whether it
+    /// became the new tip.
+    #[allow(clippy::result_large_err)]
+    pub fn append(&self, block: Block) -> Result<bool, Status> {
+        if block.hash != pow_hash(&block, block.nonce)
+            || !verify(&block, block.nonce, difficulty(&block))
+        {
+            return Err(Status::invalid_argument("Invalid proof of work"));
+        }
+
+        let mut state = self.state.write().unwrap();
+        if state.blocks.contains_key(&block.hash) {
+            return Err(Status::already_exists("Block already in the chain"));
+        }
+
+        let parent_work = match block.height {
+            0 if block.prev_hash.is_empty() => 0,
+            0 => {
+                return Err(Status::invalid_argument(
+                    "Genesis block has no previous hash",
+                ))
+            }
+            _ => {
+                let parent = state
+                    .blocks
+                    .get(&block.prev_hash)
+                    .ok_or_else(|| Status::failed_precondition("Unknown previous block"))?;
+                if block.height != parent.block.height + 1 {
+                    return Err(Status::invalid_argument(format!(
+                        "Height must be {}",
+                        parent.block.height + 1
+                    )));
+                }
+                if block.timestamp < parent.block.timestamp {
+                    return Err(Status::invalid_argument(
+                        "Timestamp is before the previous block",
+                    ));
+                }
+                parent.work
+            }
+        };
+
+        let work = parent_work.saturating_add(work(&block));
+        let best = match state.tip.as_ref() {
+            Some(tip) => work > state.blocks[tip].work,
+            None => true,
+        };
+        if best {
+            state.tip = Some(block.hash.clone());
+        }
+        state
+            .blocks
+            .insert(block.hash.clone(), Entry { block, work });
+        Ok(best)
+    }
+
+    pub fn get(&self, hash: &[u8]) -> Option<Block> {
+        let state = self.state.read().unwrap();
+        state.blocks.get(hash).map(|entry| entry.block.clone())
+    }
+
+    pub fn tip(&self) -> Option<Block> {
+        let state = self.state.read().unwrap();
+        state
+            .tip
+            .as_ref()
+            .map(|tip| state.blocks[tip].block.clone())
+    }
+
+    /// The best chain, from the genesis block to the tip.
+    pub fn blocks(&self) -> Vec<Block> {
+        let state = self.state.read().unwrap();
+        let mut blocks = Vec::new();
+        let mut next = state.tip.as_ref();
+        while let Some(entry) = next.map(|hash| &state.blocks[hash]) {
+            blocks.push(entry.block.clone());
+            next = match entry.block.height {
+                0 => None,
+                _ => Some(&entry.block.prev_hash),
+            };
+        }
+        blocks.reverse();
+        blocks
+    }
+}
+
+// expected number of hashes to mine the block
+fn work(block: &Block) -> u128 {
+    1u128.checked_shl(difficulty(block)).unwrap_or(u128::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow::pow_v1;
+
+    fn mine(chain: &Chain, data: &str, difficulty: u32) -> Block {
+        let block = Block {
+            data: data.as_bytes().to_vec(),
+            difficulty,
+            ..Default::default()
+        };
+        mined(chain.link(block))
+    }
+
+    fn mined(block: Block) -> Block {
+        let hash = pow_v1(block.clone()).unwrap();
+        Block {
+            hash: hash.hash,
+            nonce: hash.nonce,
+            extra_nonce: hash.extra_nonce,
+            ..block
+        }
+    }
+
+    #[test]
+    fn blocks_are_linked_to_the_tip() {
+        let chain = Chain::default();
+        let genesis = mine(&chain, "genesis", 4);
+        assert!(chain.append(genesis.clone()).unwrap());
+        let block = mine(&chain, "block", 4);
+        assert_eq!(block.height, 1);
+        assert_eq!(block.prev_hash, genesis.hash);
+        assert!(chain.append(block.clone()).unwrap());
+
+        assert_eq!(chain.blocks(), vec![genesis, block.clone()]);
+        assert_eq!(chain.tip(), Some(block.clone()));
+        assert_eq!(chain.get(&block.hash), Some(block));
+    }
+
+    #[test]
+    fn tampered_blocks_are_rejected() {
+        let chain = Chain::default();
+        let genesis = mine(&chain, "genesis", 4);
+        chain.append(genesis.clone()).unwrap();
+
+        let block = mine(&chain, "block", 4);
+        let tampered = Block {
+            data: b"tampered".to_vec(),
+            ..block.clone()
+        };
+        assert!(chain.append(tampered).is_err());
+        let orphan = mined(Block {
+            prev_hash: vec![0; 32],
+            ..block.clone()
+        });
+        assert!(chain.append(orphan).is_err());
+        let wrong_height = mined(Block {
+            height: 2,
+            ..block.clone()
+        });
+        assert!(chain.append(wrong_height).is_err());
+
+        chain.append(block.clone()).unwrap();
+        assert!(chain.append(block).is_err());
+    }
+
+    #[test]
+    fn fork_with_most_work_wins() {
+        let chain = Chain::default();
+        let genesis = mine(&chain, "genesis", 4);
+        chain.append(genesis).unwrap();
+
+        // two blocks on top of the genesis, then one more on the first of them
+        let a = mine(&chain, "a", 4);
+        let b = mine(&chain, "b", 4);
+        assert!(chain.append(a.clone()).unwrap());
+        assert!(!chain.append(b.clone()).unwrap());
+        let c = mine(&chain, "c", 4);
+        assert!(chain.append(c.clone()).unwrap());
+        assert_eq!(chain.tip(), Some(c));
+
+        // a single block with more work than both beats the longer fork
+        let d = mined(Block {
+            data: b"d".to_vec(),
+            difficulty: 8,
+            ..b
+        });
+        assert!(chain.append(d.clone()).unwrap());
+        assert_eq!(chain.blocks().len(), 2);
+        assert_eq!(chain.tip(), Some(d));
+    }
+}
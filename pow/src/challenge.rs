@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tonic::Status;
+
+use crate::pb::{Block, Challenge, HashAlgorithm, Solution};
+use crate::pow::{pow_hash, verify};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hands out hashcash style challenges and redeems their solutions, each one at most once.
+///
+/// Challenges are signed with a key only the issuer knows, so it doesn't have to remember the
+/// challenges it issued, only the ones redeemed and not expired yet.
+pub struct ChallengeIssuer {
+    key: [u8; 32],
+    difficulty: u32,
+    algorithm: HashAlgorithm,
+    ttl: Duration,
+    // signature of the redeemed challenges => their expiration
+    redeemed: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+impl ChallengeIssuer {
+    pub fn new(difficulty: u32, algorithm: HashAlgorithm, ttl: Duration) -> Self {
+        Self {
+            key: rand::random(),
+            difficulty,
+            algorithm,
+            ttl,
+            redeemed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn issue(&self) -> Challenge {
+        let mut challenge = Challenge {
+            data: rand::random::<[u8; 16]>().to_vec(),
+            difficulty: self.difficulty,
+            algorithm: self.algorithm as i32,
+            expires_at: now() + self.ttl.as_secs(),
+            ..Default::default()
+        };
+        challenge.signature = self.sign(&challenge);
+        challenge
+    }
+
+    /// Check a solution, returning its PoW hash. A solution is accepted only once.
+    #[allow(clippy::result_large_err)]
+    pub fn redeem(&self, solution: &Solution) -> Result<Vec<u8>, Status> {
+        let challenge = solution
+            .challenge
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("Missing challenge"))?;
+
+        let mac = self.mac(challenge);
+        if mac.verify_slice(&challenge.signature).is_err() {
+            return Err(Status::permission_denied("Invalid challenge signature"));
+        }
+
+        let now = now();
+        if challenge.expires_at < now {
+            return Err(Status::deadline_exceeded("Challenge expired"));
+        }
+
+        let block = Block {
+            extra_nonce: solution.extra_nonce,
+            ..challenge.block()
+        };
+        if !verify(&block, solution.nonce, challenge.difficulty) {
+            return Err(Status::invalid_argument("Invalid proof of work"));
+        }
+
+        let mut redeemed = self.redeemed.lock().unwrap();
+        redeemed.retain(|_, expires_at| *expires_at >= now);
+        if redeemed
+            .insert(challenge.signature.clone(), challenge.expires_at)
+            .is_some()
+        {
+            return Err(Status::already_exists("Challenge already redeemed"));
+        }
+
+        Ok(pow_hash(&block, solution.nonce))
+    }
+
+    fn sign(&self, challenge: &Challenge) -> Vec<u8> {
+        self.mac(challenge).finalize().into_bytes().to_vec()
+    }
+
+    fn mac(&self, challenge: &Challenge) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).unwrap();
+        mac.update(&challenge.data);
+        mac.update(&challenge.difficulty.to_be_bytes());
+        mac.update(&challenge.algorithm.to_be_bytes());
+        mac.update(&challenge.expires_at.to_be_bytes());
+        mac
+    }
+}
+
+impl Challenge {
+    /// The block to mine to solve the challenge.
+    pub fn block(&self) -> Block {
+        Block {
+            data: self.data.clone(),
+            difficulty: self.difficulty,
+            algorithm: self.algorithm,
+            ..Default::default()
+        }
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pow::pow_v1;
+
+    fn issuer() -> ChallengeIssuer {
+        ChallengeIssuer::new(8, HashAlgorithm::Blake3, Duration::from_secs(60))
+    }
+
+    fn solve(challenge: Challenge) -> Solution {
+        let hash = pow_v1(challenge.block()).unwrap();
+        Solution {
+            challenge: Some(challenge),
+            nonce: hash.nonce,
+            extra_nonce: hash.extra_nonce,
+        }
+    }
+
+    fn code(issuer: &ChallengeIssuer, solution: &Solution) -> tonic::Code {
+        issuer.redeem(solution).unwrap_err().code()
+    }
+
+    #[test]
+    fn solutions_should_be_redeemed_once() {
+        let issuer = issuer();
+        let solution = solve(issuer.issue());
+        let block = Block {
+            extra_nonce: solution.extra_nonce,
+            ..solution.challenge.as_ref().unwrap().block()
+        };
+        let hash = issuer.redeem(&solution).unwrap();
+        assert_eq!(hash, pow_hash(&block, solution.nonce));
+
+        assert_eq!(code(&issuer, &solution), tonic::Code::AlreadyExists);
+    }
+
+    #[test]
+    fn tampered_challenges_should_be_rejected() {
+        let issuer = issuer();
+        let solution = solve(issuer.issue());
+        let tamper = |f: fn(&mut Challenge)| {
+            let mut solution = solution.clone();
+            f(solution.challenge.as_mut().unwrap());
+            solution
+        };
+
+        let easier = tamper(|c| c.difficulty = 1);
+        assert_eq!(code(&issuer, &easier), tonic::Code::PermissionDenied);
+        let later = tamper(|c| c.expires_at += 3600);
+        assert_eq!(code(&issuer, &later), tonic::Code::PermissionDenied);
+        let other_data = tamper(|c| c.data = vec![0; 16]);
+        assert_eq!(code(&issuer, &other_data), tonic::Code::PermissionDenied);
+        let signature = tamper(|c| c.signature[0] ^= 1);
+        assert_eq!(code(&issuer, &signature), tonic::Code::PermissionDenied);
+        let other = self::issuer();
+        assert_eq!(code(&other, &solution), tonic::Code::PermissionDenied);
+        let missing = Solution {
+            challenge: None,
+            ..solution.clone()
+        };
+        assert_eq!(code(&issuer, &missing), tonic::Code::InvalidArgument);
+
+        // none of them used the challenge up
+        issuer.redeem(&solution).unwrap();
+    }
+
+    #[test]
+    fn expired_challenges_should_be_rejected() {
+        let issuer = issuer();
+        let mut challenge = issuer.issue();
+        challenge.expires_at = now() - 1;
+        challenge.signature = issuer.sign(&challenge);
+        let solution = solve(challenge);
+        assert_eq!(code(&issuer, &solution), tonic::Code::DeadlineExceeded);
+    }
+
+    #[test]
+    fn wrong_nonces_should_be_rejected() {
+        let issuer = issuer();
+        let solution = solve(issuer.issue());
+        let block = solution.challenge.as_ref().unwrap().block();
+        let nonce = (0..).find(|n| !verify(&block, *n, 8)).unwrap();
+        let wrong = Solution {
+            nonce,
+            ..solution.clone()
+        };
+        assert_eq!(code(&issuer, &wrong), tonic::Code::InvalidArgument);
+        issuer.redeem(&solution).unwrap();
+    }
+}
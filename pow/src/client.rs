@@ -1,17 +1,37 @@
-mod pb;
+use std::sync::atomic::AtomicBool;
 
 use anyhow::Result;
-use pb::pow_builder_client::*;
-use pb::*;
+use pow::auth::connect;
+use pow::pb::*;
+use pow::pow_v2;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let addr = "http://localhost:8888";
-    let mut client = PowBuilderClient::connect(addr).await?;
+    // needed when the server has tenants
+    let token = std::env::var("POW_TOKEN").ok();
+    let mut client = connect(addr.to_string(), token.as_deref()).await?;
+
+    // solve a challenge, as a client would before signing up
+    let challenge = client
+        .get_challenge(ChallengeRequest {})
+        .await?
+        .into_inner();
+    let solution = pow_v2(challenge.block(), &AtomicBool::new(false)).unwrap();
+    let res = client
+        .redeem(Solution {
+            challenge: Some(challenge),
+            nonce: solution.nonce,
+            extra_nonce: solution.extra_nonce,
+        })
+        .await?
+        .into_inner();
+    println!("Redeemed challenge - hash: {}", hex::encode(res.hash));
 
     let mut stream = client
         .subscribe(ClientInfo {
             name: "client1".to_string(),
+            ..Default::default()
         })
         .await?
         .into_inner();
@@ -19,6 +39,7 @@ async fn main() -> Result<()> {
     let res = client
         .submit(Block {
             data: b"hello world".to_vec(),
+            client: "client1".to_string(),
             ..Default::default()
         })
         .await?
@@ -26,13 +47,31 @@ async fn main() -> Result<()> {
 
     println!("Submitted {res:?}");
 
-    while let Some(result) = stream.message().await? {
+    while let Some(notification) = stream.message().await? {
+        let result = match notification.kind {
+            Some(notification::Kind::BlockHash(result)) => result,
+            Some(notification::Kind::Progress(progress)) => {
+                println!(
+                    "Progress - job: {}, tried: {}, {:.0} H/s, elapsed: {}ms, best: {}",
+                    progress.job_id,
+                    progress.tried,
+                    progress.hash_rate,
+                    progress.elapsed_ms,
+                    hex::encode(progress.best_hash)
+                );
+                continue;
+            }
+            _ => continue,
+        };
         println!(
-            "Result - id: {}, hash {}, nonce: {}",
+            "Result - id: {}, hash {}, nonce: {}, extra nonce: {}",
             hex::encode(result.id),
             hex::encode(result.hash),
-            result.nonce
+            result.nonce,
+            result.extra_nonce
         );
+        let chain = client.get_chain(ChainRequest {}).await?.into_inner();
+        println!("Chain - {} blocks", chain.blocks.len());
     }
 
     Ok(())
@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+use tonic::Status;
+
+use crate::job::Job;
+use crate::pb::{Block, BlockHash, WorkReport, WorkUnit};
+use crate::pow::{difficulty, get_block_id, pow_hash, verify, Progress};
+
+/// Nonces a worker searches at a time.
+pub const RANGE_SIZE: u64 = 1 << 24;
+// how often a mined job checks whether it was cancelled or lost workers
+const TICK: Duration = Duration::from_millis(100);
+
+type Units = mpsc::Sender<Result<WorkUnit, Status>>;
+
+// range handed out to a worker
+#[derive(Debug, Clone, Copy)]
+struct Assigned {
+    job_id: u64,
+    extra_nonce: u64,
+    nonce_start: u64,
+}
+
+struct Worker {
+    tx: Units,
+    range: Option<Assigned>,
+}
+
+struct Distributed {
+    block: Block,
+    // counts the nonces of the ranges searched
+    progress: Arc<Progress>,
+    // next range to hand out
+    extra_nonce: u64,
+    nonce_start: u64,
+    // ranges of the workers gone, handed out again first
+    abandoned: Vec<(u64, u64)>,
+    result: Option<BlockHash>,
+}
+
+impl Distributed {
+    fn new(block: Block, progress: Arc<Progress>) -> Self {
+        Self {
+            progress,
+            extra_nonce: block.extra_nonce,
+            nonce_start: 0,
+            abandoned: Vec::new(),
+            result: None,
+            block,
+        }
+    }
+
+    fn next_range(&mut self) -> (u64, u64) {
+        if let Some(range) = self.abandoned.pop() {
+            return range;
+        }
+        let range = (self.extra_nonce, self.nonce_start);
+        match self.nonce_start.checked_add(RANGE_SIZE) {
+            Some(start) if start < u64::MAX => self.nonce_start = start,
+            _ => {
+                self.extra_nonce += 1;
+                self.nonce_start = 0;
+            }
+        }
+        range
+    }
+}
+
+#[derive(Default)]
+struct State {
+    workers: HashMap<String, Worker>,
+    jobs: HashMap<u64, Distributed>,
+    // workers registering are dropped right away
+    closed: bool,
+}
+
+impl State {
+    fn abandon(&mut self, range: Option<Assigned>) {
+        if let Some(range) = range {
+            if let Some(job) = self.jobs.get_mut(&range.job_id) {
+                job.abandoned.push((range.extra_nonce, range.nonce_start));
+            }
+        }
+    }
+
+    // forget the workers which closed their stream
+    fn remove_closed(&mut self) {
+        let closed: Vec<_> = self
+            .workers
+            .iter()
+            .filter(|(_, worker)| worker.tx.is_closed())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in closed {
+            let worker = self.workers.remove(&name).unwrap();
+            self.abandon(worker.range);
+        }
+    }
+
+    // hand the next ranges of the job to the idle workers
+    fn assign(&mut self, job_id: u64) {
+        let job = self.jobs.get_mut(&job_id).unwrap();
+        let idle = self.workers.values_mut().filter(|w| w.range.is_none());
+        for worker in idle {
+            let (extra_nonce, nonce_start) = job.next_range();
+            let unit = WorkUnit {
+                job_id,
+                block: Some(Block {
+                    extra_nonce,
+                    ..job.block.clone()
+                }),
+                nonce_start,
+                nonce_end: nonce_start.saturating_add(RANGE_SIZE),
+                stop: false,
+            };
+            match worker.tx.try_send(Ok(unit)) {
+                Ok(()) => {
+                    worker.range = Some(Assigned {
+                        job_id,
+                        extra_nonce,
+                        nonce_start,
+                    })
+                }
+                // full or closed, the range goes to someone else
+                Err(_) => job.abandoned.push((extra_nonce, nonce_start)),
+            }
+        }
+    }
+
+    fn stop(&mut self, job_id: u64) {
+        for worker in self.workers.values_mut() {
+            if matches!(worker.range, Some(range) if range.job_id == job_id) {
+                let unit = WorkUnit {
+                    job_id,
+                    stop: true,
+                    ..Default::default()
+                };
+                let _ = worker.tx.try_send(Ok(unit));
+                worker.range = None;
+            }
+        }
+    }
+}
+
+/// Splits jobs into disjoint nonce ranges searched by remote workers, one range per worker at
+/// a time. The first worker finding the nonce ends the job, the others are told to stop.
+#[derive(Default)]
+pub struct Coordinator {
+    state: Mutex<State>,
+    // a worker registered or reported
+    changed: Condvar,
+}
+
+impl Coordinator {
+    /// Add a worker receiving its ranges on `tx`. A worker registering again under the same
+    /// name replaces the previous one.
+    pub fn register(&self, name: String, tx: Units) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        let worker = Worker { tx, range: None };
+        if let Some(previous) = state.workers.insert(name, worker) {
+            state.abandon(previous.range);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Number of workers registered and still connected.
+    pub fn workers(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.remove_closed();
+        state.workers.len()
+    }
+
+    /// Drop every worker, which ends their streams, and the ones registering later.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.workers.clear();
+        self.changed.notify_all();
+    }
+
+    /// Mine the job on the workers, blocking until one of them finds the nonce. Returns `None`
+    /// if the job is cancelled, or if every worker left before.
+    pub fn mine(&self, job: &Job) -> Option<BlockHash> {
+        let mut state = self.state.lock().unwrap();
+        let distributed = Distributed::new(job.block.clone(), job.progress.clone());
+        state.jobs.insert(job.id, distributed);
+        let result = loop {
+            state.remove_closed();
+            if let Some(hash) = state.jobs.get_mut(&job.id).unwrap().result.take() {
+                break Some(hash);
+            }
+            if job.is_cancelled() || state.workers.is_empty() {
+                break None;
+            }
+            state.assign(job.id);
+            state = self.changed.wait_timeout(state, TICK).unwrap().0;
+        };
+        state.stop(job.id);
+        state.jobs.remove(&job.id);
+        result
+    }
+
+    /// Record the outcome of a range. Returns false if the range wasn't expected, e.g. because
+    /// its job is over, or if the nonce found is wrong.
+    pub fn report(&self, report: &WorkReport) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let range = match state.workers.get_mut(&report.worker) {
+            Some(worker) => match worker.range {
+                Some(range)
+                    if range.job_id == report.job_id && range.nonce_start == report.nonce_start =>
+                {
+                    worker.range.take().unwrap()
+                }
+                _ => return false,
+            },
+            None => return false,
+        };
+        self.changed.notify_all();
+
+        let job = match state.jobs.get_mut(&range.job_id) {
+            Some(job) => job,
+            None => return false,
+        };
+        if !report.found {
+            job.progress.add(RANGE_SIZE);
+            return true;
+        }
+
+        let block = Block {
+            extra_nonce: range.extra_nonce,
+            ..job.block.clone()
+        };
+        let nonces = range.nonce_start..range.nonce_start.saturating_add(RANGE_SIZE);
+        if !nonces.contains(&report.nonce) || !verify(&block, report.nonce, difficulty(&block)) {
+            job.abandoned.push((range.extra_nonce, range.nonce_start));
+            return false;
+        }
+        job.result = Some(BlockHash {
+            id: get_block_id(&block),
+            hash: pow_hash(&block, report.nonce),
+            nonce: report.nonce,
+            difficulty: difficulty(&block),
+            extra_nonce: range.extra_nonce,
+            algorithm: block.algorithm,
+            ..Default::default()
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::thread;
+
+    use super::*;
+    use crate::job::Jobs;
+    use crate::pow::pow_range;
+
+    fn start(
+        jobs: &Jobs,
+        workers: &[&str],
+    ) -> (
+        Arc<Coordinator>,
+        Vec<mpsc::Receiver<Result<WorkUnit, Status>>>,
+    ) {
+        let coordinator = Arc::new(Coordinator::default());
+        let units = workers
+            .iter()
+            .map(|name| {
+                let (tx, rx) = mpsc::channel(8);
+                coordinator.register(name.to_string(), tx);
+                rx
+            })
+            .collect();
+
+        let job = jobs.create(Block {
+            data: b"hello world".to_vec(),
+            difficulty: 8,
+            ..Default::default()
+        });
+        let mining = coordinator.clone();
+        thread::spawn(move || mining.mine(&job));
+        (coordinator, units)
+    }
+
+    fn next(units: &mut mpsc::Receiver<Result<WorkUnit, Status>>) -> WorkUnit {
+        units.blocking_recv().unwrap().unwrap()
+    }
+
+    fn report(worker: &str, unit: &WorkUnit, nonce: Option<u64>) -> WorkReport {
+        WorkReport {
+            worker: worker.to_string(),
+            job_id: unit.job_id,
+            nonce_start: unit.nonce_start,
+            found: nonce.is_some(),
+            nonce: nonce.unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn first_solution_stops_the_other_workers() {
+        let jobs = Jobs::default();
+        let (coordinator, mut units) = start(&jobs, &["w1", "w2"]);
+        let w1 = next(&mut units[0]);
+        let w2 = next(&mut units[1]);
+        assert_eq!(w1.block.as_ref().unwrap().extra_nonce, 0);
+        assert_eq!(w2.block.as_ref().unwrap().extra_nonce, 0);
+        assert!(w1.nonce_end <= w2.nonce_start || w2.nonce_end <= w1.nonce_start);
+
+        let block = w1.block.clone().unwrap();
+        let nonces = w1.nonce_start..w1.nonce_end;
+        let hash = pow_range(block, nonces, &AtomicBool::new(false)).unwrap();
+        assert!(coordinator.report(&report("w1", &w1, Some(hash.nonce))));
+
+        let stop = next(&mut units[1]);
+        assert!(stop.stop);
+        assert_eq!(stop.job_id, w2.job_id);
+        // the search of the job is over
+        assert!(!coordinator.report(&report("w2", &w2, None)));
+    }
+
+    #[test]
+    fn exhausted_and_wrong_ranges_are_searched_again() {
+        let jobs = Jobs::default();
+        let (coordinator, mut units) = start(&jobs, &["w1", "w2"]);
+        let w1 = next(&mut units[0]);
+        let w2 = next(&mut units[1]);
+
+        // w1 is given the next range, w2 its own range back
+        assert!(coordinator.report(&report("w1", &w1, None)));
+        let third = next(&mut units[0]);
+        assert_eq!(
+            third.nonce_start,
+            w1.nonce_start.max(w2.nonce_start) + RANGE_SIZE
+        );
+        assert!(!coordinator.report(&report("w2", &w2, Some(w2.nonce_end))));
+        assert_eq!(next(&mut units[1]).nonce_start, w2.nonce_start);
+
+        assert!(jobs.cancel(w1.job_id));
+        assert!(next(&mut units[0]).stop);
+    }
+}
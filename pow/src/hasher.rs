@@ -0,0 +1,199 @@
+use std::ops::Range;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha256};
+
+use crate::pow::leading_zero_bits;
+
+/// Hash function a block is mined with. The PoW hash of a block is
+/// `hash(header + extra nonce (BE) + nonce (BE))`, always 32 bytes long, the header being the
+/// height (BE), timestamp (BE), previous hash and data of the block.
+pub trait PowHasher: Sync {
+    /// What can be computed once for all the nonces of a given data and extra nonce.
+    type State: Clone + Send + Sync;
+
+    fn prepare(&self, data: &[u8], extra_nonce: u64) -> Self::State;
+
+    fn hash(&self, state: &Self::State, nonce: u64) -> Vec<u8>;
+
+    /// Nonces hashed by a work unit of the batched search.
+    const BATCH_SIZE: u64 = 1024;
+
+    /// Leading zero bits and nonce of the best hash of `nonces`, stopping at the first one with
+    /// at least `difficulty` bits. `None` if there are no nonces. Hashers can override it to
+    /// share the setup of a hash across the whole batch.
+    fn search(
+        &self,
+        state: &Self::State,
+        nonces: Range<u64>,
+        difficulty: u32,
+    ) -> Option<(u32, u64)> {
+        search_each(nonces, difficulty, |n| {
+            leading_zero_bits(&self.hash(state, n))
+        })
+    }
+}
+
+fn search_each(
+    nonces: Range<u64>,
+    difficulty: u32,
+    mut bits: impl FnMut(u64) -> u32,
+) -> Option<(u32, u64)> {
+    let mut best: Option<(u32, u64)> = None;
+    for nonce in nonces {
+        let bits = bits(nonce);
+        if !matches!(best, Some((best, _)) if bits <= best) {
+            best = Some((bits, nonce));
+        }
+        if bits >= difficulty {
+            break;
+        }
+    }
+    best
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Blake3;
+
+#[derive(Debug, Clone)]
+pub struct Blake3State {
+    // data and extra nonce already hashed
+    hasher: blake3::Hasher,
+    // data and extra nonce followed by room for the nonce, if they fit in a single chunk
+    input: Option<Vec<u8>>,
+}
+
+impl PowHasher for Blake3 {
+    type State = Blake3State;
+
+    fn prepare(&self, data: &[u8], extra_nonce: u64) -> Self::State {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(data);
+        hasher.update(&extra_nonce.to_be_bytes()[..]);
+        let len = data.len() + 16;
+        let input = (len <= blake3::CHUNK_LEN)
+            .then(|| [data, &extra_nonce.to_be_bytes()[..], &[0; 8][..]].concat());
+        Blake3State { hasher, input }
+    }
+
+    fn hash(&self, state: &Self::State, nonce: u64) -> Vec<u8> {
+        let mut hasher = state.hasher.clone();
+        hasher.update(&nonce.to_be_bytes()[..]);
+        hasher.finalize().as_bytes().to_vec()
+    }
+
+    // A single chunk is cheaper to hash from scratch than the hasher is to clone, so the nonce
+    // is written in place into the same input for the whole batch. blake3 doesn't expose its
+    // multi-input SIMD hashing, which would hash several nonces per call.
+    fn search(
+        &self,
+        state: &Self::State,
+        nonces: Range<u64>,
+        difficulty: u32,
+    ) -> Option<(u32, u64)> {
+        let mut input = match &state.input {
+            Some(input) => input.clone(),
+            None => {
+                return search_each(nonces, difficulty, |n| {
+                    leading_zero_bits(&self.hash(state, n))
+                })
+            }
+        };
+        let at = input.len() - 8;
+        search_each(nonces, difficulty, |n| {
+            input[at..].copy_from_slice(&n.to_be_bytes());
+            leading_zero_bits(blake3::hash(&input).as_bytes())
+        })
+    }
+}
+
+/// Bitcoin style double SHA-256.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sha256d;
+
+impl PowHasher for Sha256d {
+    type State = Sha256;
+
+    fn prepare(&self, data: &[u8], extra_nonce: u64) -> Self::State {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        hasher.update(&extra_nonce.to_be_bytes()[..]);
+        hasher
+    }
+
+    fn hash(&self, state: &Self::State, nonce: u64) -> Vec<u8> {
+        let mut hasher = state.clone();
+        hasher.update(&nonce.to_be_bytes()[..]);
+        Sha256::digest(hasher.finalize()).to_vec()
+    }
+}
+
+// the nonces already make every input unique, a fixed salt is enough
+const SALT: &[u8] = b"rust-usage-pow";
+
+/// Argon2id using 1 MiB of memory per hash.
+#[derive(Clone)]
+pub struct Argon2id {
+    argon2: Argon2<'static>,
+}
+
+impl Default for Argon2id {
+    fn default() -> Self {
+        let params = Params::new(1024, 1, 1, Some(32)).unwrap();
+        Self {
+            argon2: Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+        }
+    }
+}
+
+// memory-hard hashes can't start from a partial state, they take the whole input every time
+impl PowHasher for Argon2id {
+    type State = Vec<u8>;
+
+    // a few ms per hash, keep the search quick to cancel
+    const BATCH_SIZE: u64 = 8;
+
+    fn prepare(&self, data: &[u8], extra_nonce: u64) -> Self::State {
+        [data, &extra_nonce.to_be_bytes()[..]].concat()
+    }
+
+    fn hash(&self, state: &Self::State, nonce: u64) -> Vec<u8> {
+        let input = [&state[..], &nonce.to_be_bytes()[..]].concat();
+        let mut out = vec![0; 32];
+        self.argon2
+            .hash_password_into(&input, SALT, &mut out)
+            .unwrap();
+        out
+    }
+}
+
+/// scrypt with N = 2^10, r = 8, p = 1, i.e. 1 MiB of memory per hash.
+#[derive(Debug, Clone)]
+pub struct Scrypt {
+    params: scrypt::Params,
+}
+
+impl Default for Scrypt {
+    fn default() -> Self {
+        Self {
+            params: scrypt::Params::new(10, 8, 1).unwrap(),
+        }
+    }
+}
+
+impl PowHasher for Scrypt {
+    type State = Vec<u8>;
+
+    const BATCH_SIZE: u64 = 8;
+
+    fn prepare(&self, data: &[u8], extra_nonce: u64) -> Self::State {
+        [data, &extra_nonce.to_be_bytes()[..]].concat()
+    }
+
+    fn hash(&self, state: &Self::State, nonce: u64) -> Vec<u8> {
+        let input = [&state[..], &nonce.to_be_bytes()[..]].concat();
+        let mut out = vec![0; 32];
+        scrypt::scrypt(&input, SALT, &self.params, &mut out).unwrap();
+        out
+    }
+}
@@ -0,0 +1,93 @@
+//! The standard gRPC health service, `grpc.health.v1.Health`, reporting the `PowBuilder`
+//! service and the server as a whole (the empty service name).
+
+use std::pin::Pin;
+
+use futures::Stream;
+use tokio::sync::{mpsc, watch};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::pb::health::health_check_response::ServingStatus;
+use crate::pb::health::health_server::{Health, HealthServer};
+use crate::pb::health::{HealthCheckRequest, HealthCheckResponse};
+
+/// Services the health of which is reported.
+pub const SERVICES: &[&str] = &["", "abi.PowBuilder"];
+
+/// Sets the status reported by the `HealthService`.
+#[derive(Debug)]
+pub struct HealthReporter {
+    tx: watch::Sender<ServingStatus>,
+}
+
+impl HealthReporter {
+    /// Report `NOT_SERVING` for good, which also ends the `Watch` streams.
+    pub fn shutdown(self) {
+        self.tx.send_replace(ServingStatus::NotServing);
+    }
+}
+
+#[derive(Debug)]
+pub struct HealthService {
+    status: watch::Receiver<ServingStatus>,
+}
+
+/// A health service reporting `SERVING` until the reporter says otherwise.
+pub fn health_service() -> (HealthReporter, HealthServer<HealthService>) {
+    let (tx, status) = watch::channel(ServingStatus::Serving);
+    (
+        HealthReporter { tx },
+        HealthServer::new(HealthService { status }),
+    )
+}
+
+fn response(status: ServingStatus) -> HealthCheckResponse {
+    HealthCheckResponse {
+        status: status as i32,
+    }
+}
+
+#[tonic::async_trait]
+impl Health for HealthService {
+    type WatchStream =
+        Pin<Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send + Sync>>;
+
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        let service = request.into_inner().service;
+        if !SERVICES.contains(&service.as_str()) {
+            return Err(Status::not_found(format!("Unknown service {:?}", service)));
+        }
+        Ok(Response::new(response(*self.status.borrow())))
+    }
+
+    async fn watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let service = request.into_inner().service;
+        let (tx, rx) = mpsc::channel(1);
+        if !SERVICES.contains(&service.as_str()) {
+            let _ = tx.send(Ok(response(ServingStatus::ServiceUnknown))).await;
+            return Ok(Response::new(Box::pin(ReceiverStream::new(rx))));
+        }
+
+        // the current status, then every change until the reporter is dropped
+        let mut status = self.status.clone();
+        tokio::spawn(async move {
+            loop {
+                let current = *status.borrow_and_update();
+                if tx.send(Ok(response(current))).await.is_err() {
+                    return;
+                }
+                if status.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
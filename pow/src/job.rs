@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tonic::Status;
+
+use crate::pb::{Block, BlockHash, JobProgress};
+use crate::pow::Progress;
+
+/// A block submitted to the PoW engine.
+#[derive(Debug)]
+pub struct Job {
+    pub id: u64,
+    pub block: Block,
+    // set by `Jobs::cancel`, checked by the search
+    pub cancelled: Arc<AtomicBool>,
+    // updated by the search
+    pub progress: Arc<Progress>,
+}
+
+impl Job {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Sent back by the PoW engine.
+#[derive(Debug)]
+pub enum JobEvent {
+    /// Sent periodically while the job is mined, to the client which submitted the block.
+    Progress {
+        client: String,
+        progress: JobProgress,
+    },
+    Finished(Box<JobResult>),
+}
+
+/// Outcome of a job.
+#[derive(Debug)]
+pub struct JobResult {
+    pub job_id: u64,
+    // block as submitted, linked to its parent in the chain
+    pub block: Block,
+    pub result: Result<BlockHash, Status>,
+}
+
+impl JobResult {
+    /// The submitted block with its PoW hash and nonces, if it was mined.
+    pub fn mined(&self) -> Option<Block> {
+        let hash = self.result.as_ref().ok()?;
+        Some(Block {
+            hash: hash.hash.clone(),
+            nonce: hash.nonce,
+            extra_nonce: hash.extra_nonce,
+            ..self.block.clone()
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Pending {
+    client: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// Hands out job ids and keeps the client and the cancellation flag of the jobs not finished
+/// yet.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, Pending>>,
+}
+
+impl Jobs {
+    pub fn create(&self, block: Block) -> Job {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let pending = Pending {
+            client: block.client.clone(),
+            cancelled: cancelled.clone(),
+        };
+        self.pending.lock().unwrap().insert(id, pending);
+        Job {
+            id,
+            block,
+            cancelled,
+            progress: Arc::new(Progress::default()),
+        }
+    }
+
+    /// Ask a job to stop. Returns false if the job is unknown or already finished.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.pending.lock().unwrap().get(&id) {
+            Some(pending) => {
+                pending.cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Ask every job not finished yet to stop.
+    pub fn cancel_all(&self) {
+        for pending in self.pending.lock().unwrap().values() {
+            pending.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Client which submitted a job not finished yet.
+    pub fn client(&self, id: u64) -> Option<String> {
+        let pending = self.pending.lock().unwrap();
+        pending.get(&id).map(|pending| pending.client.clone())
+    }
+
+    pub fn finish(&self, id: u64) {
+        self.pending.lock().unwrap().remove(&id);
+    }
+}
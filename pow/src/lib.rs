@@ -0,0 +1,19 @@
+pub mod auth;
+pub mod chain;
+pub mod challenge;
+pub mod coordinator;
+pub mod hasher;
+pub mod health;
+pub mod job;
+pub mod pb;
+pub mod pow;
+pub mod scheduler;
+pub mod server;
+pub mod service;
+pub mod store;
+pub mod stratum;
+pub mod worker;
+
+pub use crate::pow::{
+    difficulty, pow_hash, pow_range, pow_v1, pow_v2, pow_v3, pow_v3_with_progress, verify,
+};
@@ -7,18 +7,225 @@ pub struct Block {
     #[prost(bytes = "vec", tag = "2")]
     pub hash: ::prost::alloc::vec::Vec<u8>,
     /// tune nonce to get hash with x 0 prefix -> 0x000abcdfadbd
-    #[prost(uint32, tag = "3")]
-    pub nonce: u32,
+    #[prost(uint64, tag = "3")]
+    pub nonce: u64,
+    /// number of leading zero bits the hash must have, 0 means the server default
+    #[prost(uint32, tag = "4")]
+    pub difficulty: u32,
+    /// first extra nonce to search, it is incremented whenever the whole nonce space is exhausted
+    #[prost(uint64, tag = "5")]
+    pub extra_nonce: u64,
+    /// name of the subscribed client the result is delivered to
+    #[prost(string, tag = "6")]
+    pub client: ::prost::alloc::string::String,
+    /// blocks with a higher priority are mined first
+    #[prost(uint32, tag = "7")]
+    pub priority: u32,
+    #[prost(enumeration = "HashAlgorithm", tag = "8")]
+    pub algorithm: i32,
+    /// PoW hash of the block it is mined on top of, set by the server, empty for the genesis block
+    #[prost(bytes = "vec", tag = "9")]
+    pub prev_hash: ::prost::alloc::vec::Vec<u8>,
+    /// set by the server, the genesis block is at height 0
+    #[prost(uint64, tag = "10")]
+    pub height: u64,
+    /// unix timestamp in seconds, set by the server when the block is submitted
+    #[prost(uint64, tag = "11")]
+    pub timestamp: u64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BlockStatus {
     #[prost(uint32, tag = "1")]
     pub code: u32,
+    /// id of the job mining the submitted block
+    #[prost(uint64, tag = "2")]
+    pub job_id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobInfo {
+    #[prost(uint64, tag = "1")]
+    pub job_id: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Verification {
+    #[prost(bool, tag = "1")]
+    pub valid: bool,
+    /// PoW hash of the block
+    #[prost(bytes = "vec", tag = "2")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChallengeRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Challenge {
+    /// random data to mine
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint32, tag = "2")]
+    pub difficulty: u32,
+    #[prost(enumeration = "HashAlgorithm", tag = "3")]
+    pub algorithm: i32,
+    /// unix timestamp in seconds after which the challenge can't be redeemed
+    #[prost(uint64, tag = "4")]
+    pub expires_at: u64,
+    /// issuer signature of all the fields above
+    #[prost(bytes = "vec", tag = "5")]
+    pub signature: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Solution {
+    #[prost(message, optional, tag = "1")]
+    pub challenge: ::core::option::Option<Challenge>,
+    #[prost(uint64, tag = "2")]
+    pub nonce: u64,
+    #[prost(uint64, tag = "3")]
+    pub extra_nonce: u64,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Redemption {
+    /// PoW hash of the solution
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ChainRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockChain {
+    #[prost(message, repeated, tag = "1")]
+    pub blocks: ::prost::alloc::vec::Vec<Block>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BlockRequest {
+    /// PoW hash of the block
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: ::prost::alloc::vec::Vec<u8>,
+}
+/// ids are scoped to the client which submitted the block, the same data submitted by two
+/// clients are two blocks with their own result
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResultRequest {
+    /// block id, as in `BlockHash`
+    #[prost(bytes = "vec", tag = "1")]
+    pub id: ::prost::alloc::vec::Vec<u8>,
+    /// client the block was submitted for, the tenant by default
+    #[prost(string, tag = "2")]
+    pub client: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatusRequest {}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueueStatus {
+    /// jobs waiting to be mined
+    #[prost(uint32, tag = "1")]
+    pub queued: u32,
+    /// jobs being mined
+    #[prost(uint32, tag = "2")]
+    pub running: u32,
+    /// max number of jobs mined at the same time
+    #[prost(uint32, tag = "3")]
+    pub concurrency: u32,
+    /// max number of queued jobs, submissions are rejected beyond it
+    #[prost(uint32, tag = "4")]
+    pub capacity: u32,
+    /// registered mining workers
+    #[prost(uint32, tag = "5")]
+    pub workers: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkerInfo {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkUnit {
+    #[prost(uint64, tag = "1")]
+    pub job_id: u64,
+    /// block to mine, with the extra nonce to search with
+    #[prost(message, optional, tag = "2")]
+    pub block: ::core::option::Option<Block>,
+    /// nonces to search, `nonce_end` excluded
+    #[prost(uint64, tag = "3")]
+    pub nonce_start: u64,
+    #[prost(uint64, tag = "4")]
+    pub nonce_end: u64,
+    /// the job is over, stop searching its range
+    #[prost(bool, tag = "5")]
+    pub stop: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkReport {
+    #[prost(string, tag = "1")]
+    pub worker: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub job_id: u64,
+    /// start of the range searched
+    #[prost(uint64, tag = "3")]
+    pub nonce_start: u64,
+    #[prost(bool, tag = "4")]
+    pub found: bool,
+    #[prost(uint64, tag = "5")]
+    pub nonce: u64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ClientInfo {
+    /// unique among the connected subscribers
     #[prost(string, tag = "1")]
     pub name: ::prost::alloc::string::String,
+    /// receive the results of every client, not only the blocks submitted under `name`
+    #[prost(bool, tag = "2")]
+    pub broadcast: bool,
+    /// start with the stored results, which can be delivered again if they were mined while
+    /// subscribing
+    #[prost(bool, tag = "3")]
+    pub replay: bool,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscriptionStatus {
+    /// 0, or 404 if there is no such subscriber
+    #[prost(uint32, tag = "1")]
+    pub code: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Notification {
+    #[prost(oneof = "notification::Kind", tags = "1, 2, 3")]
+    pub kind: ::core::option::Option<notification::Kind>,
+}
+/// Nested message and enum types in `Notification`.
+pub mod notification {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Kind {
+        #[prost(message, tag = "1")]
+        BlockHash(super::BlockHash),
+        #[prost(message, tag = "2")]
+        Heartbeat(super::Heartbeat),
+        #[prost(message, tag = "3")]
+        Progress(super::JobProgress),
+    }
+}
+/// sent periodically while a job is mined
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobProgress {
+    #[prost(uint64, tag = "1")]
+    pub job_id: u64,
+    /// nonces hashed so far
+    #[prost(uint64, tag = "2")]
+    pub tried: u64,
+    /// hashes per second since the job started
+    #[prost(double, tag = "3")]
+    pub hash_rate: f64,
+    /// milliseconds since the job started
+    #[prost(uint64, tag = "4")]
+    pub elapsed_ms: u64,
+    /// hash with the most leading zero bits so far
+    #[prost(bytes = "vec", tag = "5")]
+    pub best_hash: ::prost::alloc::vec::Vec<u8>,
+}
+/// sent periodically on an idle subscription, to tell stale clients apart
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Heartbeat {
+    /// unix timestamp in seconds
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct BlockHash {
@@ -29,10 +236,33 @@ pub struct BlockHash {
     #[prost(bytes = "vec", tag = "2")]
     pub hash: ::prost::alloc::vec::Vec<u8>,
     /// nonce
-    #[prost(uint32, tag = "3")]
-    pub nonce: u32,
+    #[prost(uint64, tag = "3")]
+    pub nonce: u64,
+    /// leading zero bits the hash was searched for
+    #[prost(uint32, tag = "4")]
+    pub difficulty: u32,
+    /// extra nonce the nonce was found with
+    #[prost(uint64, tag = "5")]
+    pub extra_nonce: u64,
+    /// job which mined the block
+    #[prost(uint64, tag = "6")]
+    pub job_id: u64,
+    #[prost(enumeration = "HashAlgorithm", tag = "7")]
+    pub algorithm: i32,
+}
+/// hash function a block is mined with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum HashAlgorithm {
+    Blake3 = 0,
+    /// Bitcoin style double SHA-256
+    Sha256d = 1,
+    /// memory-hard
+    Argon2id = 2,
+    /// memory-hard
+    Scrypt = 3,
 }
-/// Generated client implementations.
+#[doc = r" Generated client implementations."]
 pub mod pow_builder_client {
     #![allow(unused_variables, dead_code, missing_docs)]
     use tonic::codegen::*;
@@ -40,7 +270,7 @@ pub mod pow_builder_client {
         inner: tonic::client::Grpc<T>,
     }
     impl PowBuilderClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
+        #[doc = r" Attempt to create a new client by connecting to a given endpoint."]
         pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
         where
             D: std::convert::TryInto<tonic::transport::Endpoint>,
@@ -68,7 +298,7 @@ pub mod pow_builder_client {
         pub async fn subscribe(
             &mut self,
             request: impl tonic::IntoRequest<super::ClientInfo>,
-        ) -> Result<tonic::Response<tonic::codec::Streaming<super::BlockHash>>, tonic::Status>
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::Notification>>, tonic::Status>
         {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::new(
@@ -82,6 +312,21 @@ pub mod pow_builder_client {
                 .server_streaming(request.into_request(), path, codec)
                 .await
         }
+        #[doc = " close the stream of a subscriber"]
+        pub async fn unsubscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ClientInfo>,
+        ) -> Result<tonic::Response<super::SubscriptionStatus>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Unsubscribe");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
         pub async fn submit(
             &mut self,
             request: impl tonic::IntoRequest<super::Block>,
@@ -96,6 +341,158 @@ pub mod pow_builder_client {
             let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Submit");
             self.inner.unary(request.into_request(), path, codec).await
         }
+        pub async fn cancel(
+            &mut self,
+            request: impl tonic::IntoRequest<super::JobInfo>,
+        ) -> Result<tonic::Response<super::BlockStatus>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Cancel");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StatusRequest>,
+        ) -> Result<tonic::Response<super::QueueStatus>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Status");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " check the block `nonce` (and `hash` if set) against the block difficulty"]
+        pub async fn verify(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Block>,
+        ) -> Result<tonic::Response<super::Verification>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Verify");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " hashcash style puzzles: get a challenge, mine its block and redeem the solution once"]
+        pub async fn get_challenge(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ChallengeRequest>,
+        ) -> Result<tonic::Response<super::Challenge>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/GetChallenge");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn redeem(
+            &mut self,
+            request: impl tonic::IntoRequest<super::Solution>,
+        ) -> Result<tonic::Response<super::Redemption>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Redeem");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " the best chain of mined blocks, from the genesis block to the tip. Tenants get the blocks of"]
+        #[doc = " the other clients without their data and client."]
+        pub async fn get_chain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ChainRequest>,
+        ) -> Result<tonic::Response<super::BlockChain>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/GetChain");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        pub async fn get_block(
+            &mut self,
+            request: impl tonic::IntoRequest<super::BlockRequest>,
+        ) -> Result<tonic::Response<super::Block>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/GetBlock");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " result of the last block mined with this id, or why it was not mined, kept across restarts."]
+        #[doc = " Tenants only get the results of their own blocks."]
+        pub async fn get_result(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ResultRequest>,
+        ) -> Result<tonic::Response<super::BlockHash>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/GetResult");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
+        #[doc = " register a mining worker, the server streams it the nonce ranges to search. Only the"]
+        #[doc = " tenants running workers can register them and report."]
+        pub async fn work(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WorkerInfo>,
+        ) -> Result<tonic::Response<tonic::codec::Streaming<super::WorkUnit>>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Work");
+            self.inner
+                .server_streaming(request.into_request(), path, codec)
+                .await
+        }
+        #[doc = " result of the search of a range, found or not"]
+        pub async fn report(
+            &mut self,
+            request: impl tonic::IntoRequest<super::WorkReport>,
+        ) -> Result<tonic::Response<super::BlockStatus>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/abi.PowBuilder/Report");
+            self.inner.unary(request.into_request(), path, codec).await
+        }
     }
     impl<T: Clone> Clone for PowBuilderClient<T> {
         fn clone(&self) -> Self {
@@ -110,16 +507,15 @@ pub mod pow_builder_client {
         }
     }
 }
-/// Generated server implementations.
+#[doc = r" Generated server implementations."]
 pub mod pow_builder_server {
     #![allow(unused_variables, dead_code, missing_docs)]
     use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with
-    /// PowBuilderServer.
+    #[doc = "Generated trait containing gRPC methods that should be implemented for use with PowBuilderServer."]
     #[async_trait]
     pub trait PowBuilder: Send + Sync + 'static {
-        /// Server streaming response type for the Subscribe method.
-        type SubscribeStream: futures_core::Stream<Item = Result<super::BlockHash, tonic::Status>>
+        #[doc = "Server streaming response type for the Subscribe method."]
+        type SubscribeStream: futures_core::Stream<Item = Result<super::Notification, tonic::Status>>
             + Send
             + Sync
             + 'static;
@@ -127,10 +523,69 @@ pub mod pow_builder_server {
             &self,
             request: tonic::Request<super::ClientInfo>,
         ) -> Result<tonic::Response<Self::SubscribeStream>, tonic::Status>;
+        #[doc = " close the stream of a subscriber"]
+        async fn unsubscribe(
+            &self,
+            request: tonic::Request<super::ClientInfo>,
+        ) -> Result<tonic::Response<super::SubscriptionStatus>, tonic::Status>;
         async fn submit(
             &self,
             request: tonic::Request<super::Block>,
         ) -> Result<tonic::Response<super::BlockStatus>, tonic::Status>;
+        async fn cancel(
+            &self,
+            request: tonic::Request<super::JobInfo>,
+        ) -> Result<tonic::Response<super::BlockStatus>, tonic::Status>;
+        async fn status(
+            &self,
+            request: tonic::Request<super::StatusRequest>,
+        ) -> Result<tonic::Response<super::QueueStatus>, tonic::Status>;
+        #[doc = " check the block `nonce` (and `hash` if set) against the block difficulty"]
+        async fn verify(
+            &self,
+            request: tonic::Request<super::Block>,
+        ) -> Result<tonic::Response<super::Verification>, tonic::Status>;
+        #[doc = " hashcash style puzzles: get a challenge, mine its block and redeem the solution once"]
+        async fn get_challenge(
+            &self,
+            request: tonic::Request<super::ChallengeRequest>,
+        ) -> Result<tonic::Response<super::Challenge>, tonic::Status>;
+        async fn redeem(
+            &self,
+            request: tonic::Request<super::Solution>,
+        ) -> Result<tonic::Response<super::Redemption>, tonic::Status>;
+        #[doc = " the best chain of mined blocks, from the genesis block to the tip. Tenants get the blocks of"]
+        #[doc = " the other clients without their data and client."]
+        async fn get_chain(
+            &self,
+            request: tonic::Request<super::ChainRequest>,
+        ) -> Result<tonic::Response<super::BlockChain>, tonic::Status>;
+        async fn get_block(
+            &self,
+            request: tonic::Request<super::BlockRequest>,
+        ) -> Result<tonic::Response<super::Block>, tonic::Status>;
+        #[doc = " result of the last block mined with this id, or why it was not mined, kept across restarts."]
+        #[doc = " Tenants only get the results of their own blocks."]
+        async fn get_result(
+            &self,
+            request: tonic::Request<super::ResultRequest>,
+        ) -> Result<tonic::Response<super::BlockHash>, tonic::Status>;
+        #[doc = "Server streaming response type for the Work method."]
+        type WorkStream: futures_core::Stream<Item = Result<super::WorkUnit, tonic::Status>>
+            + Send
+            + Sync
+            + 'static;
+        #[doc = " register a mining worker, the server streams it the nonce ranges to search. Only the"]
+        #[doc = " tenants running workers can register them and report."]
+        async fn work(
+            &self,
+            request: tonic::Request<super::WorkerInfo>,
+        ) -> Result<tonic::Response<Self::WorkStream>, tonic::Status>;
+        #[doc = " result of the search of a range, found or not"]
+        async fn report(
+            &self,
+            request: tonic::Request<super::WorkReport>,
+        ) -> Result<tonic::Response<super::BlockStatus>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct PowBuilderServer<T: PowBuilder> {
@@ -168,7 +623,7 @@ pub mod pow_builder_server {
                     #[allow(non_camel_case_types)]
                     struct SubscribeSvc<T: PowBuilder>(pub Arc<T>);
                     impl<T: PowBuilder> tonic::server::ServerStreamingService<super::ClientInfo> for SubscribeSvc<T> {
-                        type Response = super::BlockHash;
+                        type Response = super::Notification;
                         type ResponseStream = T::SubscribeStream;
                         type Future =
                             BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
@@ -197,6 +652,37 @@ pub mod pow_builder_server {
                     };
                     Box::pin(fut)
                 }
+                "/abi.PowBuilder/Unsubscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct UnsubscribeSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::ClientInfo> for UnsubscribeSvc<T> {
+                        type Response = super::SubscriptionStatus;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ClientInfo>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).unsubscribe(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = UnsubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/abi.PowBuilder/Submit" => {
                     #[allow(non_camel_case_types)]
                     struct SubmitSvc<T: PowBuilder>(pub Arc<T>);
@@ -225,6 +711,315 @@ pub mod pow_builder_server {
                     };
                     Box::pin(fut)
                 }
+                "/abi.PowBuilder/Cancel" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::JobInfo> for CancelSvc<T> {
+                        type Response = super::BlockStatus;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::JobInfo>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).cancel(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = CancelSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/Status" => {
+                    #[allow(non_camel_case_types)]
+                    struct StatusSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::StatusRequest> for StatusSvc<T> {
+                        type Response = super::QueueStatus;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StatusRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).status(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = StatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/Verify" => {
+                    #[allow(non_camel_case_types)]
+                    struct VerifySvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::Block> for VerifySvc<T> {
+                        type Response = super::Verification;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(&mut self, request: tonic::Request<super::Block>) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).verify(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = VerifySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/GetChallenge" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetChallengeSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::ChallengeRequest> for GetChallengeSvc<T> {
+                        type Response = super::Challenge;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ChallengeRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_challenge(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetChallengeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/Redeem" => {
+                    #[allow(non_camel_case_types)]
+                    struct RedeemSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::Solution> for RedeemSvc<T> {
+                        type Response = super::Redemption;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::Solution>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).redeem(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = RedeemSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/GetChain" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetChainSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::ChainRequest> for GetChainSvc<T> {
+                        type Response = super::BlockChain;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ChainRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_chain(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetChainSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/GetBlock" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetBlockSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::BlockRequest> for GetBlockSvc<T> {
+                        type Response = super::Block;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::BlockRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_block(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetBlockSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/GetResult" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetResultSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::ResultRequest> for GetResultSvc<T> {
+                        type Response = super::BlockHash;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ResultRequest>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).get_result(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = GetResultSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/Work" => {
+                    #[allow(non_camel_case_types)]
+                    struct WorkSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::ServerStreamingService<super::WorkerInfo> for WorkSvc<T> {
+                        type Response = super::WorkUnit;
+                        type ResponseStream = T::WorkStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WorkerInfo>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).work(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1;
+                        let inner = inner.0;
+                        let method = WorkSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/abi.PowBuilder/Report" => {
+                    #[allow(non_camel_case_types)]
+                    struct ReportSvc<T: PowBuilder>(pub Arc<T>);
+                    impl<T: PowBuilder> tonic::server::UnaryService<super::WorkReport> for ReportSvc<T> {
+                        type Response = super::BlockStatus;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::WorkReport>,
+                        ) -> Self::Future {
+                            let inner = self.0.clone();
+                            let fut = async move { (*inner).report(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let interceptor = inner.1.clone();
+                        let inner = inner.0;
+                        let method = ReportSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = if let Some(interceptor) = interceptor {
+                            tonic::server::Grpc::with_interceptor(codec, interceptor)
+                        } else {
+                            tonic::server::Grpc::new(codec)
+                        };
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
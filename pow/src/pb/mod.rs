@@ -1,2 +1,5 @@
 mod abi;
 pub use abi::*;
+
+#[path = "grpc.health.v1.rs"]
+pub mod health;
@@ -1,48 +1,374 @@
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
 use rayon::prelude::*;
 
-use crate::pb::{Block, BlockHash};
-pub const PREFIX_ZERO: &[u8] = &[0, 0, 0];
+use crate::hasher::*;
+use crate::pb::{Block, BlockHash, HashAlgorithm};
+
+/// Leading zero bits of the hash when the block doesn't ask for a difficulty, the same as the
+/// three zero bytes prefix it used to be.
+pub const DEFAULT_DIFFICULTY: u32 = 24;
+/// Every PoW hash has 256 bits.
+pub const MAX_DIFFICULTY: u32 = 256;
+
+/// Progress of a batched search, updated after every batch.
+#[derive(Debug, Default)]
+pub struct Progress {
+    tried: AtomicU64,
+    // leading zero bits and PoW hash of the best hash so far
+    best: Mutex<Option<(u32, Vec<u8>)>>,
+}
+
+impl Progress {
+    /// Number of nonces hashed.
+    pub fn tried(&self) -> u64 {
+        self.tried.load(Ordering::Relaxed)
+    }
+
+    /// The hash with the most leading zero bits so far, empty before the first batch.
+    pub fn best_hash(&self) -> Vec<u8> {
+        let best = self.best.lock().unwrap();
+        best.as_ref()
+            .map(|(_, hash)| hash.clone())
+            .unwrap_or_default()
+    }
+
+    /// Count `tried` more nonces, hashed elsewhere.
+    pub fn add(&self, tried: u64) {
+        self.tried.fetch_add(tried, Ordering::Relaxed);
+    }
+
+    // the hash is only computed when it beats the best one
+    fn update(&self, tried: u64, bits: u32, hash: impl FnOnce() -> Vec<u8>) {
+        self.add(tried);
+        let beaten = |best: &Option<(u32, Vec<u8>)>| !matches!(best, Some((b, _)) if bits <= *b);
+        if beaten(&self.best.lock().unwrap()) {
+            let hash = hash();
+            let mut best = self.best.lock().unwrap();
+            if beaten(&best) {
+                *best = Some((bits, hash));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Search {
+    Sequential,
+    Parallel,
+    Batched,
+}
 
-#[allow(dead_code)]
 pub fn pow_v1(block: Block) -> Option<BlockHash> {
-    let hasher = blake3_base_hash(&block.data);
-    let nonce = (0..u32::MAX).find(|n| {
-        let hash = blake3_hash(hasher.clone(), *n);
-        &hash[..PREFIX_ZERO.len()] == PREFIX_ZERO
-    });
-    nonce.map(|n| {
-        let id = get_block_id(&block);
-        let hash = blake3_hash(hasher, n);
-        BlockHash { id, hash, nonce: n }
-    })
-}
-
-pub fn pow_v2(block: Block) -> Option<BlockHash> {
-    let hasher = blake3_base_hash(&block.data);
-    let nonce = (0..u32::MAX).into_par_iter().find_any(|n| {
-        let hash = blake3_hash(hasher.clone(), *n);
-        &hash[..PREFIX_ZERO.len()] == PREFIX_ZERO
-    });
-    nonce.map(|n| {
-        let id = get_block_id(&block);
-        let hash = blake3_hash(hasher, n);
-        BlockHash { id, hash, nonce: n }
-    })
-}
-
-fn get_block_id(block: &Block) -> Vec<u8> {
+    let cancel = AtomicBool::new(false);
+    mine(block, 0..u64::MAX, true, &cancel, None, Search::Sequential)
+}
+
+/// Search the nonce on all the rayon threads. Returns `None` as soon as possible once `cancel`
+/// is set.
+pub fn pow_v2(block: Block, cancel: &AtomicBool) -> Option<BlockHash> {
+    mine(block, 0..u64::MAX, true, cancel, None, Search::Parallel)
+}
+
+/// Like `pow_v2`, but every rayon work unit searches a whole batch of nonces, amortizing the
+/// scheduling, the cancellation check and the hasher setup over the batch.
+pub fn pow_v3(block: Block, cancel: &AtomicBool) -> Option<BlockHash> {
+    mine(block, 0..u64::MAX, true, cancel, None, Search::Batched)
+}
+
+/// `pow_v3` reporting its progress in `progress`.
+pub fn pow_v3_with_progress(
+    block: Block,
+    cancel: &AtomicBool,
+    progress: &Progress,
+) -> Option<BlockHash> {
+    mine(
+        block,
+        0..u64::MAX,
+        true,
+        cancel,
+        Some(progress),
+        Search::Batched,
+    )
+}
+
+/// Search `nonces` with the extra nonce of the block only, the way `pow_v3` does. This is how a
+/// worker mines its share of a job.
+pub fn pow_range(block: Block, nonces: Range<u64>, cancel: &AtomicBool) -> Option<BlockHash> {
+    mine(block, nonces, false, cancel, None, Search::Batched)
+}
+
+/// Difficulty the block must be mined at, in leading zero bits.
+pub fn difficulty(block: &Block) -> u32 {
+    match block.difficulty {
+        0 => DEFAULT_DIFFICULTY,
+        n => n.min(MAX_DIFFICULTY),
+    }
+}
+
+/// Check that the PoW hash of the block with `nonce` has at least `difficulty` leading zero
+/// bits. The extra nonce and the hash algorithm are taken from the block. This costs a single
+/// hash.
+pub fn verify(block: &Block, nonce: u64, difficulty: u32) -> bool {
+    difficulty <= MAX_DIFFICULTY && leading_zero_bits(&pow_hash(block, nonce)) >= difficulty
+}
+
+/// PoW hash of the block with `nonce`, as found by the search.
+pub fn pow_hash(block: &Block, nonce: u64) -> Vec<u8> {
+    match block.algorithm() {
+        HashAlgorithm::Blake3 => hash_with(&Blake3, block, nonce),
+        HashAlgorithm::Sha256d => hash_with(&Sha256d, block, nonce),
+        HashAlgorithm::Argon2id => hash_with(&Argon2id::default(), block, nonce),
+        HashAlgorithm::Scrypt => hash_with(&Scrypt::default(), block, nonce),
+    }
+}
+
+fn hash_with<H: PowHasher>(hasher: &H, block: &Block, nonce: u64) -> Vec<u8> {
+    hasher.hash(&hasher.prepare(&header(block), block.extra_nonce), nonce)
+}
+
+// What the PoW commits to besides the nonces: the position of the block in the chain and its
+// data. Height 0 has no previous hash, every other height a 32 bytes one.
+fn header(block: &Block) -> Vec<u8> {
+    [
+        &block.height.to_be_bytes()[..],
+        &block.timestamp.to_be_bytes()[..],
+        &block.prev_hash,
+        &block.data,
+    ]
+    .concat()
+}
+
+fn mine(
+    block: Block,
+    nonces: Range<u64>,
+    rollover: bool,
+    cancel: &AtomicBool,
+    progress: Option<&Progress>,
+    search: Search,
+) -> Option<BlockHash> {
+    let extra_nonces = match rollover {
+        true => block.extra_nonce..u64::MAX,
+        false => block.extra_nonce..block.extra_nonce.saturating_add(1),
+    };
+    match block.algorithm() {
+        HashAlgorithm::Blake3 => mine_with(
+            &Blake3,
+            block,
+            nonces,
+            extra_nonces,
+            cancel,
+            progress,
+            search,
+        ),
+        HashAlgorithm::Sha256d => mine_with(
+            &Sha256d,
+            block,
+            nonces,
+            extra_nonces,
+            cancel,
+            progress,
+            search,
+        ),
+        HashAlgorithm::Argon2id => {
+            let hasher = Argon2id::default();
+            mine_with(
+                &hasher,
+                block,
+                nonces,
+                extra_nonces,
+                cancel,
+                progress,
+                search,
+            )
+        }
+        HashAlgorithm::Scrypt => {
+            let hasher = Scrypt::default();
+            mine_with(
+                &hasher,
+                block,
+                nonces,
+                extra_nonces,
+                cancel,
+                progress,
+                search,
+            )
+        }
+    }
+}
+
+// Search `nonces` with every extra nonce in turn, moving to the next one when they are all
+// exhausted.
+fn mine_with<H: PowHasher>(
+    hasher: &H,
+    block: Block,
+    nonces: Range<u64>,
+    extra_nonces: Range<u64>,
+    cancel: &AtomicBool,
+    progress: Option<&Progress>,
+    search: Search,
+) -> Option<BlockHash> {
+    let difficulty = difficulty(&block);
+    let header = header(&block);
+    let batches = nonces.end.saturating_sub(nonces.start) / H::BATCH_SIZE;
+    for extra_nonce in extra_nonces {
+        let state = hasher.prepare(&header, extra_nonce);
+        let found = |n: &u64| {
+            // finding "something" is the fastest way to stop every thread
+            if cancel.load(Ordering::Relaxed) {
+                return true;
+            }
+            leading_zero_bits(&hasher.hash(&state, *n)) >= difficulty
+        };
+        let nonce = match search {
+            Search::Sequential => nonces.clone().find(found),
+            Search::Parallel => nonces.clone().into_par_iter().find_any(found),
+            Search::Batched => (0..=batches).into_par_iter().find_map_any(|batch| {
+                if cancel.load(Ordering::Relaxed) {
+                    return Some(0);
+                }
+                let start = nonces.start + batch * H::BATCH_SIZE;
+                let end = start.saturating_add(H::BATCH_SIZE).min(nonces.end);
+                let (bits, nonce) = hasher.search(&state, start..end, difficulty)?;
+                let found = bits >= difficulty;
+                if let Some(progress) = progress {
+                    let tried = if found {
+                        nonce - start + 1
+                    } else {
+                        end - start
+                    };
+                    progress.update(tried, bits, || hasher.hash(&state, nonce));
+                }
+                match found {
+                    true => Some(nonce),
+                    false => None,
+                }
+            }),
+        };
+        if cancel.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        if let Some(nonce) = nonce {
+            return Some(BlockHash {
+                id: get_block_id(&block),
+                hash: hasher.hash(&state, nonce),
+                nonce,
+                difficulty,
+                extra_nonce,
+                algorithm: block.algorithm,
+                ..Default::default()
+            });
+        }
+    }
+    None
+}
+
+pub(crate) fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+pub(crate) fn get_block_id(block: &Block) -> Vec<u8> {
     let hash = blake3::hash(&block.data);
     hash.as_bytes().to_vec()
 }
 
-// pow hash: Block data + nonce (BE) => hash
-fn blake3_hash(mut hasher: blake3::Hasher, nonce: u32) -> Vec<u8> {
-    hasher.update(&nonce.to_be_bytes()[..]);
-    hasher.finalize().as_bytes().to_vec()
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(difficulty: u32, algorithm: HashAlgorithm) -> Block {
+        Block {
+            data: b"hello world".to_vec(),
+            difficulty,
+            algorithm: algorithm as i32,
+            ..Default::default()
+        }
+    }
+
+    // the first nonce of the first extra nonce meeting the difficulty
+    #[test]
+    fn pow_v1_should_find_the_first_nonce() {
+        let hash = pow_v1(block(16, HashAlgorithm::Blake3)).unwrap();
+        assert_eq!((hash.nonce, hash.extra_nonce), (49090, 0));
+        assert_eq!(
+            hex::encode(&hash.hash),
+            "0000d28dbc85363713bfe53b706ab41d6f49b07d3a443701db0eb855bb39b7f0"
+        );
+        assert_eq!(hash.id, blake3::hash(b"hello world").as_bytes().to_vec());
+        assert_eq!(hash.difficulty, 16);
+
+        let hash = pow_v1(block(16, HashAlgorithm::Sha256d)).unwrap();
+        assert_eq!((hash.nonce, hash.extra_nonce), (162216, 0));
+        assert_eq!(
+            hex::encode(&hash.hash),
+            "00005db62f7624817c75b745e7c5d4b33a54b5a19c19cbf0617d854fce68afba"
+        );
+    }
+
+    // any nonce meeting the difficulty, found by whichever thread gets there first
+    #[test]
+    fn pow_v2_should_find_a_valid_nonce() {
+        for algorithm in [HashAlgorithm::Blake3, HashAlgorithm::Sha256d] {
+            let block = block(16, algorithm);
+            let first = pow_v1(block.clone()).unwrap();
+            let hash = pow_v2(block.clone(), &AtomicBool::new(false)).unwrap();
+            assert!(hash.nonce >= first.nonce);
+            assert_eq!(hash.hash, pow_hash(&block, hash.nonce));
+            assert!(verify(&block, hash.nonce, 16));
+            assert_eq!(hash.id, first.id);
+        }
+
+        let cancelled = AtomicBool::new(true);
+        assert_eq!(pow_v2(block(16, HashAlgorithm::Blake3), &cancelled), None);
+    }
+
+    // a handful of hashes only, these are slow on purpose
+    #[test]
+    fn memory_hard_blocks_should_be_mined_and_verify() {
+        for algorithm in [HashAlgorithm::Argon2id, HashAlgorithm::Scrypt] {
+            let block = block(4, algorithm);
+            let hash = pow_v3(block.clone(), &AtomicBool::new(false)).unwrap();
+            assert_eq!(hash.hash.len(), 32);
+            assert_eq!(hash.hash, pow_hash(&block, hash.nonce));
+            assert!(leading_zero_bits(&hash.hash) >= 4);
+            assert!(verify(&block, hash.nonce, 4));
+
+            // the nonce is only valid for the algorithm it was mined with
+            let blake3 = Block {
+                algorithm: HashAlgorithm::Blake3 as i32,
+                ..block
+            };
+            assert_ne!(pow_hash(&blake3, hash.nonce), hash.hash);
+        }
+    }
 
-fn blake3_base_hash(data: &[u8]) -> blake3::Hasher {
-    let mut hasher = blake3::Hasher::new();
-    hasher.update(data);
-    hasher
+    #[test]
+    fn exhausted_nonces_should_roll_over_the_extra_nonce() {
+        let cancel = AtomicBool::new(false);
+        let block = block(12, HashAlgorithm::Blake3);
+        let hash = mine(block.clone(), 0..256, true, &cancel, None, Search::Sequential).unwrap();
+        assert_eq!((hash.nonce, hash.extra_nonce), (251, 21));
+        assert_eq!(
+            hex::encode(&hash.hash),
+            "00089099777662620be73720bd879bd00efd1108498bd1aa48914e93a1c9b2e9"
+        );
+        // without rolling over, only the extra nonce of the block is searched
+        assert_eq!(pow_range(block.clone(), 0..256, &cancel), None);
+        let block = Block {
+            extra_nonce: hash.extra_nonce,
+            ..block
+        };
+        assert!(verify(&block, hash.nonce, 12));
+        assert_eq!(pow_range(block, 0..256, &cancel), Some(hash));
+    }
 }
@@ -0,0 +1,278 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::mpsc::{self as std_mpsc, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tonic::Status;
+
+use crate::chain::Chain;
+use crate::coordinator::Coordinator;
+use crate::job::{Job, JobEvent, JobResult, Jobs};
+use crate::pb::{BlockHash, JobProgress, QueueStatus};
+use crate::pow::pow_v3_with_progress;
+
+/// How often the progress of the running jobs is sent.
+pub const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// jobs are ordered by priority, then by submission order
+struct Queued(Job);
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .block
+            .priority
+            .cmp(&other.0.block.priority)
+            .then_with(|| other.0.id.cmp(&self.0.id))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    queue: BinaryHeap<Queued>,
+    running: usize,
+    // no more jobs are taken, the threads exit once the queue is empty
+    closed: bool,
+}
+
+/// Runs up to `concurrency` jobs at a time, highest priority first. All the jobs share the
+/// global rayon pool, so running several of them splits the cores between them.
+pub struct Scheduler {
+    concurrency: usize,
+    capacity: usize,
+    jobs: Arc<Jobs>,
+    state: Mutex<State>,
+    // a job was queued or finished, or the scheduler was closed
+    available: Condvar,
+}
+
+impl Scheduler {
+    /// Start `concurrency` threads sending the progress and the result of every job to `tx`. At
+    /// most `capacity` jobs wait in the queue. Jobs are mined by the workers of `coordinator`
+    /// if there are any, on top of `chain`, which they are appended to. The threads exit once
+    /// the scheduler is shut down, dropping `tx`.
+    pub fn start(
+        concurrency: usize,
+        capacity: usize,
+        jobs: Arc<Jobs>,
+        coordinator: Arc<Coordinator>,
+        chain: Arc<Chain>,
+        tx: mpsc::Sender<JobEvent>,
+    ) -> Arc<Self> {
+        let scheduler = Arc::new(Self {
+            concurrency,
+            capacity,
+            jobs: jobs.clone(),
+            state: Mutex::new(State::default()),
+            available: Condvar::new(),
+        });
+
+        for _ in 0..concurrency {
+            let scheduler = scheduler.clone();
+            let jobs = jobs.clone();
+            let coordinator = coordinator.clone();
+            let chain = chain.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                while let Some(mut job) = scheduler.next() {
+                    let reporter = Reporter::start(&job, tx.clone());
+                    let msg = run(&mut job, &coordinator, &chain);
+                    drop(reporter);
+                    jobs.finish(job.id);
+                    scheduler.state.lock().unwrap().running -= 1;
+                    scheduler.available.notify_all();
+                    if tx.blocking_send(JobEvent::Finished(Box::new(msg))).is_err() {
+                        println!("Result of job {} dropped, the service is gone", job.id);
+                        break;
+                    }
+                }
+            });
+        }
+
+        scheduler
+    }
+
+    /// Queue a job, or give it back when the queue is full or the scheduler is closed.
+    #[allow(clippy::result_large_err)]
+    pub fn submit(&self, job: Job) -> Result<(), Job> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed || state.queue.len() >= self.capacity {
+            return Err(job);
+        }
+        state.queue.push(Queued(job));
+        self.available.notify_one();
+        Ok(())
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let state = self.state.lock().unwrap();
+        QueueStatus {
+            queued: state.queue.len() as u32,
+            running: state.running as u32,
+            concurrency: self.concurrency as u32,
+            capacity: self.capacity as u32,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    /// Stop taking jobs and cancel the queued ones. They are still handed to the threads, which
+    /// send their results once done with the running jobs.
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        for Queued(job) in state.queue.iter() {
+            self.jobs.cancel(job.id);
+        }
+        self.available.notify_all();
+    }
+
+    /// Close the scheduler and give the running jobs `grace` to finish before cancelling them.
+    pub async fn drain(self: Arc<Self>, grace: Duration) {
+        self.close();
+        let _ = tokio::task::spawn_blocking(move || {
+            let state = self.state.lock().unwrap();
+            let (state, _) = self
+                .available
+                .wait_timeout_while(state, grace, |state| state.running > 0)
+                .unwrap();
+            if state.running > 0 {
+                println!("Cancelling {} running jobs", state.running);
+                drop(state);
+                self.jobs.cancel_all();
+            }
+        })
+        .await;
+    }
+
+    // block until a job is available, `None` once closed and drained
+    fn next(&self) -> Option<Job> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(Queued(job)) = state.queue.pop() {
+                state.running += 1;
+                return Some(job);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.available.wait(state).unwrap();
+        }
+    }
+}
+
+// Sends the progress of a job every `PROGRESS_INTERVAL` until dropped.
+struct Reporter {
+    stop: std_mpsc::Sender<()>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Reporter {
+    fn start(job: &Job, tx: mpsc::Sender<JobEvent>) -> Self {
+        let (stop, stopped) = std_mpsc::channel();
+        let job_id = job.id;
+        let client = job.block.client.clone();
+        let progress = job.progress.clone();
+        let thread = thread::spawn(move || {
+            let started = Instant::now();
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(PROGRESS_INTERVAL) {
+                let elapsed = started.elapsed();
+                let tried = progress.tried();
+                let progress = JobProgress {
+                    job_id,
+                    tried,
+                    hash_rate: tried as f64 / elapsed.as_secs_f64(),
+                    elapsed_ms: elapsed.as_millis() as u64,
+                    best_hash: progress.best_hash(),
+                };
+                // progress is dropped rather than holding the search back when nobody reads it
+                let client = client.clone();
+                let _ = tx.try_send(JobEvent::Progress { client, progress });
+            }
+        });
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Reporter {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Mine the block of the job on top of the tip of the chain. Other jobs mined at the same time
+// may have moved the tip in the meantime, the block is mined again on the new tip then, so that
+// every mined block ends up in the best chain.
+fn run(job: &mut Job, coordinator: &Coordinator, chain: &Chain) -> JobResult {
+    loop {
+        job.block = chain.link(job.block.clone());
+        let msg = mine(job, coordinator);
+        let block = match msg.mined() {
+            Some(block) => block,
+            None => return msg,
+        };
+        match chain.append(block) {
+            Ok(true) => return msg,
+            Ok(false) => println!("Job {} lost the tip, mining it again", job.id),
+            Err(err) => {
+                println!("Failed to append job {} to the chain: {}", job.id, err);
+                return msg;
+            }
+        }
+    }
+}
+
+fn mine(job: &Job, coordinator: &Coordinator) -> JobResult {
+    // a job cancelled while queued is never started
+    let mut result = None;
+    if !job.is_cancelled() && coordinator.workers() > 0 {
+        result = coordinator.mine(job);
+    }
+    // mined here when there are no workers, or when they all left
+    if result.is_none() && !job.is_cancelled() {
+        result = pow_v3_with_progress(job.block.clone(), &job.cancelled, &job.progress);
+    }
+    let result = match result {
+        Some(hash) => Ok(BlockHash {
+            job_id: job.id,
+            ..hash
+        }),
+        None if job.is_cancelled() => Err(Status::cancelled(format!("Job {} cancelled", job.id))),
+        None => Err(Status::resource_exhausted(
+            "Failed to find a suitable hash".to_string(),
+        )),
+    };
+
+    JobResult {
+        job_id: job.id,
+        block: job.block.clone(),
+        result,
+    }
+}
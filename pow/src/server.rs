@@ -1,131 +1,146 @@
-mod pb;
-mod pow;
+//! The PoW engine wired to the gRPC services, served on any incoming stream of connections:
+//! a TCP listener on an ephemeral port in the tests, the server address in the `server` binary.
 
-use anyhow::Result;
-use futures::Stream;
-use pb::{pow_builder_server::*, *};
-use pow::*;
-use std::{collections::HashMap, pin::Pin, sync::Arc, thread};
-use tokio::sync::{mpsc, RwLock};
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{transport::Server, Request, Response, Status};
-
-const CHANNEL_SIZE: usize = 8;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-#[derive(Debug, Default)]
-struct Shared {
-    clients: HashMap<String, mpsc::Sender<Result<BlockHash, Status>>>,
+use futures::Stream;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
+use tonic::transport::server::Connected;
+use tonic::transport::{Error, Server};
+
+use crate::auth::Auth;
+use crate::chain::Chain;
+use crate::coordinator::Coordinator;
+use crate::health::health_service;
+use crate::job::Jobs;
+use crate::pb::pow_builder_server::PowBuilderServer;
+use crate::pow::DEFAULT_DIFFICULTY;
+use crate::scheduler::Scheduler;
+use crate::service::{PowService, CHANNEL_SIZE, HEARTBEAT_INTERVAL};
+use crate::store::Store;
+
+/// Settings of a server.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of blocks mined at the same time.
+    pub concurrent_jobs: usize,
+    /// Number of blocks waiting to be mined before submissions are rejected.
+    pub queue_size: usize,
+    /// Leading zero bits of the blocks submitted without a difficulty.
+    pub difficulty: u32,
+    /// Messages buffered by every subscriber and worker stream.
+    pub channel_size: usize,
+    /// Events buffered between the PoW engine and the gRPC service.
+    pub event_channel_size: usize,
+    pub heartbeat: Duration,
+    /// Time the running jobs are given to finish on shutdown before being cancelled.
+    pub grace_period: Duration,
+    /// Directory of the job and result store, a temporary one if `None`.
+    pub store: Option<PathBuf>,
+    /// Tenants allowed to use `PowBuilder`, anyone if `None`.
+    pub auth: Option<Arc<Auth>>,
 }
 
-impl Shared {
-    async fn broadcast(&self, msg: Option<BlockHash>) {
-        let msg = msg.ok_or(Status::resource_exhausted(
-            "Failed to find a suitable hash".to_string(),
-        ));
-        for (name, tx) in &self.clients {
-            match tx.send(msg.clone()).await {
-                Ok(_) => (),
-                Err(err) => {
-                    println!(
-                        "Broacast error to {} with {:?}. Error: {:?}",
-                        name, msg, err
-                    );
-                }
-            }
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            concurrent_jobs: 2,
+            queue_size: 64,
+            difficulty: DEFAULT_DIFFICULTY,
+            channel_size: CHANNEL_SIZE,
+            event_channel_size: CHANNEL_SIZE,
+            heartbeat: HEARTBEAT_INTERVAL,
+            grace_period: Duration::from_secs(30),
+            store: None,
+            auth: None,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct PowService {
-    // send block to PoW engine
-    tx: mpsc::Sender<Block>,
-    shared: Arc<RwLock<Shared>>,
+/// A started PoW engine, waiting to be served.
+pub struct PowServer {
+    svc: PowService,
+    scheduler: Arc<Scheduler>,
+    auth: Option<Arc<Auth>>,
+    grace_period: Duration,
 }
 
-#[tonic::async_trait]
-impl PowBuilder for PowService {
-    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<BlockHash, Status>> + Send + Sync>>;
-
-    async fn subscribe(
-        &self,
-        request: Request<ClientInfo>,
-    ) -> Result<Response<Self::SubscribeStream>, Status> {
-        let name = request.into_inner().name;
-
-        let rx = {
-            let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
-            self.shared.write().await.clients.insert(name, tx);
-            rx
+impl PowServer {
+    /// Open the store, then start the scheduler threads and the service event loop, which
+    /// needs a tokio runtime.
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        let store = match &config.store {
+            Some(path) => Store::open(path)?,
+            None => Store::temporary()?,
         };
 
-        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+        // PoW -> grpc
+        let (tx, rx) = mpsc::channel(config.event_channel_size);
+
+        let jobs = Arc::new(Jobs::default());
+        let coordinator = Arc::new(Coordinator::default());
+        let chain = Arc::new(Chain::default());
+        let scheduler = Scheduler::start(
+            config.concurrent_jobs,
+            config.queue_size,
+            jobs.clone(),
+            coordinator.clone(),
+            chain.clone(),
+            tx,
+        );
+        let mut svc = PowService::new(scheduler.clone(), rx, jobs, coordinator, chain, store)
+            .with_heartbeat(config.heartbeat)
+            .with_channel_size(config.channel_size)
+            .with_difficulty(config.difficulty);
+        if let Some(auth) = &config.auth {
+            svc = svc.with_auth(auth.clone());
+        }
+
+        Ok(Self {
+            svc,
+            scheduler,
+            auth: config.auth.clone(),
+            grace_period: config.grace_period,
+        })
     }
 
-    async fn submit(&self, request: Request<Block>) -> Result<Response<BlockStatus>, Status> {
-        let block = request.into_inner();
-        match self.tx.send(block.clone()).await {
-            Ok(()) => Ok(Response::new(BlockStatus { code: 0 })),
-            Err(err) => {
-                println!(
-                    "Failed to submit {:?} to PoW engine. Error: {:?}",
-                    block, err
-                );
-                Ok(Response::new(BlockStatus { code: 500 }))
-            }
-        }
+    /// The chain the mined blocks are appended to.
+    pub fn chain(&self) -> Arc<Chain> {
+        self.svc.chain()
     }
-}
 
-impl PowService {
-    pub fn new(tx: mpsc::Sender<Block>, mut rx: mpsc::Receiver<Option<BlockHash>>) -> Self {
-        let server = Self {
-            shared: Arc::new(RwLock::new(Shared::default())),
-            tx,
+    /// Serve `PowBuilder` and the health service on the connections of `incoming` until
+    /// `signal` completes. The health service doesn't need a token. Then queued jobs are cancelled,
+    /// running ones finish within the grace period, the streams end and the server stops.
+    pub async fn serve<I, IO, IE, F>(self, incoming: I, signal: F) -> Result<(), Error>
+    where
+        I: Stream<Item = Result<IO, IE>>,
+        IO: AsyncRead + AsyncWrite + Connected + Unpin + Send + 'static,
+        IE: Into<Box<dyn std::error::Error + Send + Sync>>,
+        F: Future<Output = ()>,
+    {
+        let (health, health_svc) = health_service();
+        let scheduler = self.scheduler;
+        let grace_period = self.grace_period;
+        let shutdown = async move {
+            signal.await;
+            scheduler.close();
+            health.shutdown();
+            scheduler.drain(grace_period).await;
         };
 
-        let shared = server.shared.clone();
-
-        tokio::spawn(async move {
-            while let Some(msg) = rx.recv().await {
-                shared.read().await.broadcast(msg).await;
-            }
-        });
-        server
+        let svc = match &self.auth {
+            Some(auth) => PowBuilderServer::with_interceptor(self.svc, auth.interceptor()),
+            None => PowBuilderServer::new(self.svc),
+        };
+        Server::builder()
+            .add_service(health_svc)
+            .add_service(svc)
+            .serve_with_incoming_shutdown(incoming, shutdown)
+            .await
     }
 }
-
-async fn start_server(addr: &str) -> Result<()> {
-    let addr = addr.parse().unwrap();
-
-    // grpc -> PoW
-    let (tx1, mut rx1) = mpsc::channel(CHANNEL_SIZE);
-
-    // PoW -> grpc
-    let (tx2, rx2) = mpsc::channel(CHANNEL_SIZE);
-
-    thread::spawn(move || {
-        while let Some(block) = rx1.blocking_recv() {
-            let result = pow_v2(block);
-            tx2.blocking_send(result).unwrap();
-        }
-    });
-
-    let svc = PowService::new(tx1, rx2);
-
-    Server::builder()
-        .add_service(PowBuilderServer::new(svc))
-        .serve(addr)
-        .await?;
-
-    Ok(())
-}
-
-#[tokio::main]
-async fn main() -> Result<()> {
-    let addr = "0.0.0.0:8888";
-    println!("Listening on {:?}", addr);
-    start_server(addr).await?;
-
-    Ok(())
-}
\ No newline at end of file
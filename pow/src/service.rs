@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::Stream;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, RwLock};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::auth::{Auth, Tenant};
+use crate::chain::Chain;
+use crate::challenge::{now, ChallengeIssuer};
+use crate::coordinator::Coordinator;
+use crate::job::*;
+use crate::pb::pow_builder_server::*;
+use crate::pb::*;
+use crate::pow::*;
+use crate::scheduler::Scheduler;
+use crate::store::Store;
+
+/// Messages buffered by default by the subscriber and worker streams, and by the PoW -> gRPC
+/// channel.
+pub const CHANNEL_SIZE: usize = 8;
+// difficulty of the challenges, a fraction of a second on a laptop
+const CHALLENGE_DIFFICULTY: u32 = 20;
+const CHALLENGE_TTL: Duration = Duration::from_secs(60);
+/// How often the subscribers get a heartbeat by default, the ones gone are removed when it
+/// fails.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug)]
+struct Subscriber {
+    tx: mpsc::Sender<Result<Notification, Status>>,
+    // receives the results of every client
+    broadcast: bool,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    clients: HashMap<String, Subscriber>,
+    // the PoW engine is gone, no results will come anymore
+    closed: bool,
+}
+
+impl Shared {
+    // a name can be reused once the subscriber using it is gone
+    #[allow(clippy::result_large_err)]
+    fn subscribe(&mut self, name: String, sub: Subscriber) -> Result<(), Status> {
+        if self.closed {
+            return Err(Status::unavailable("Server is shutting down"));
+        }
+        self.remove_closed();
+        if self.clients.contains_key(&name) {
+            return Err(Status::already_exists(format!(
+                "Client {} is already subscribed",
+                name
+            )));
+        }
+        self.clients.insert(name, sub);
+        Ok(())
+    }
+
+    // dropping the sender ends the stream of the subscriber
+    fn unsubscribe(&mut self, name: &str) -> bool {
+        self.clients.remove(name).is_some()
+    }
+
+    fn remove_closed(&mut self) {
+        self.clients.retain(|_, sub| !sub.tx.is_closed());
+    }
+
+    // end the streams of every subscriber
+    fn close(&mut self) {
+        self.closed = true;
+        self.clients.clear();
+    }
+
+    // deliver the notification to the client which submitted the block, and to the broadcast
+    // subscribers, without waiting for any of them. Progress is dropped when a subscriber is
+    // not keeping up, the next one supersedes it. Otherwise the ones gone or not keeping up are
+    // removed: their stream ends and they can subscribe again with a replay of the results.
+    fn dispatch(&mut self, client: &str, notification: Result<Notification, Status>) {
+        let progress = matches!(
+            notification,
+            Ok(Notification {
+                kind: Some(notification::Kind::Progress(_))
+            })
+        );
+        self.clients.retain(|name, sub| {
+            if name != client && !sub.broadcast {
+                return true;
+            }
+            match sub.tx.try_send(notification.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) if progress => true,
+                Err(TrySendError::Full(_)) => {
+                    println!("Client {} is not keeping up, removing it", name);
+                    false
+                }
+                Err(TrySendError::Closed(_)) => {
+                    println!("Client {} is gone, {:?} not delivered", name, notification);
+                    false
+                }
+            }
+        });
+    }
+}
+
+// Send heartbeats until the subscriber is removed or gone. The task only keeps a weak sender,
+// so that removing the subscriber ends its stream.
+async fn heartbeat(
+    tx: mpsc::WeakSender<Result<Notification, Status>>,
+    shared: Arc<RwLock<Shared>>,
+    period: Duration,
+) {
+    let mut interval = tokio::time::interval(period);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let tx = match tx.upgrade() {
+            Some(tx) => tx,
+            None => return,
+        };
+        let heartbeat = Notification {
+            kind: Some(notification::Kind::Heartbeat(Heartbeat {
+                timestamp: now(),
+            })),
+        };
+        // a full buffer already tells the subscriber the stream is alive
+        if let Err(TrySendError::Closed(_)) = tx.try_send(Ok(heartbeat)) {
+            shared.write().await.remove_closed();
+            return;
+        }
+    }
+}
+
+/// The `PowBuilder` gRPC service.
+pub struct PowService {
+    // queue blocks for the PoW engine
+    scheduler: Arc<Scheduler>,
+    jobs: Arc<Jobs>,
+    coordinator: Arc<Coordinator>,
+    challenges: ChallengeIssuer,
+    // mined blocks
+    chain: Arc<Chain>,
+    // submitted blocks and results
+    store: Store,
+    // tenants and their quotas, anyone can use the service if `None`
+    auth: Option<Arc<Auth>>,
+    shared: Arc<RwLock<Shared>>,
+    heartbeat: Duration,
+    channel_size: usize,
+    // of the blocks submitted without one
+    difficulty: u32,
+}
+
+#[tonic::async_trait]
+impl PowBuilder for PowService {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<Notification, Status>> + Send + Sync>>;
+    type WorkStream = Pin<Box<dyn Stream<Item = Result<WorkUnit, Status>> + Send + Sync>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<ClientInfo>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let tenant = self.tenant(&request)?;
+        let ClientInfo {
+            name,
+            broadcast,
+            replay,
+        } = request.into_inner();
+        if name.is_empty() {
+            return Err(Status::invalid_argument("Client name is required"));
+        }
+        check_client(tenant, &name)?;
+        if tenant.is_some() && broadcast {
+            return Err(Status::permission_denied(
+                "Tenants only get the results of their own blocks",
+            ));
+        }
+        // the results are read under the lock, so that the ones mined afterwards are dispatched
+        // once subscribed, and queued right away: the channel has room for them on top of
+        // `channel_size`, so that the replay doesn't count as not keeping up
+        let rx = {
+            let mut shared = self.shared.write().await;
+            let results = match (replay, broadcast) {
+                (false, _) => vec![],
+                (true, false) => self.store.results(Some(&name)).map_err(internal)?,
+                (true, true) => self.store.results(None).map_err(internal)?,
+            };
+            let (tx, rx) = mpsc::channel(self.channel_size + results.len());
+            for hash in results {
+                let kind = notification::Kind::BlockHash(hash);
+                let _ = tx.try_send(Ok(Notification { kind: Some(kind) }));
+            }
+            let weak = tx.downgrade();
+            shared.subscribe(name, Subscriber { tx, broadcast })?;
+            tokio::spawn(heartbeat(weak, self.shared.clone(), self.heartbeat));
+            rx
+        };
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: Request<ClientInfo>,
+    ) -> Result<Response<SubscriptionStatus>, Status> {
+        let tenant = self.tenant(&request)?;
+        let name = request.into_inner().name;
+        check_client(tenant, &name)?;
+        let code = match self.shared.write().await.unsubscribe(&name) {
+            true => 0,
+            false => 404,
+        };
+        Ok(Response::new(SubscriptionStatus { code }))
+    }
+
+    async fn submit(&self, request: Request<Block>) -> Result<Response<BlockStatus>, Status> {
+        let tenant = self.tenant(&request)?;
+        let mut block = request.into_inner();
+        if block.difficulty > MAX_DIFFICULTY {
+            return Err(Status::invalid_argument(format!(
+                "Difficulty must be at most {} bits",
+                MAX_DIFFICULTY
+            )));
+        }
+        if let (Some(tenant), true) = (tenant, block.client.is_empty()) {
+            block.client = tenant.name.clone();
+        }
+        if block.client.is_empty() {
+            return Err(Status::invalid_argument(
+                "Client name is required to deliver the result",
+            ));
+        }
+        if block.difficulty == 0 {
+            block.difficulty = self.difficulty;
+        }
+        check_client(tenant, &block.client)?;
+        if let (Some(auth), Some(tenant)) = (&self.auth, tenant) {
+            auth.submit(tenant, difficulty(&block))?;
+        }
+
+        let job = self.jobs.create(block.clone());
+        let job_id = job.id;
+        let submitted = self.scheduler.submit(job);
+        // the quotas only count the blocks queued
+        if let (Some(auth), Some(tenant), true) = (&self.auth, tenant, submitted.is_err()) {
+            auth.refund(tenant);
+        }
+        match submitted {
+            // only the blocks accepted are kept
+            Ok(()) => match self.store.add_job(&block) {
+                Ok(()) => Ok(Response::new(BlockStatus { code: 0, job_id })),
+                Err(err) => {
+                    self.jobs.cancel(job_id);
+                    Err(internal(err))
+                }
+            },
+            Err(job) => {
+                self.jobs.finish(job.id);
+                if self.scheduler.is_closed() {
+                    return Err(Status::unavailable("Server is shutting down"));
+                }
+                Err(Status::resource_exhausted(format!(
+                    "PoW engine is busy, {} blocks already queued",
+                    self.scheduler.status().capacity
+                )))
+            }
+        }
+    }
+
+    async fn cancel(&self, request: Request<JobInfo>) -> Result<Response<BlockStatus>, Status> {
+        let tenant = self.tenant(&request)?;
+        let job_id = request.into_inner().job_id;
+        if let Some(client) = self.jobs.client(job_id) {
+            check_client(tenant, &client)?;
+        }
+        let code = match self.jobs.cancel(job_id) {
+            true => 0,
+            false => 404,
+        };
+        Ok(Response::new(BlockStatus { code, job_id }))
+    }
+
+    async fn verify(&self, request: Request<Block>) -> Result<Response<Verification>, Status> {
+        let block = request.into_inner();
+        let hash = pow_hash(&block, block.nonce);
+        let valid = (block.hash.is_empty() || block.hash == hash)
+            && verify(&block, block.nonce, difficulty(&block));
+        Ok(Response::new(Verification { valid, hash }))
+    }
+
+    async fn get_challenge(
+        &self,
+        _request: Request<ChallengeRequest>,
+    ) -> Result<Response<Challenge>, Status> {
+        Ok(Response::new(self.challenges.issue()))
+    }
+
+    async fn redeem(&self, request: Request<Solution>) -> Result<Response<Redemption>, Status> {
+        let hash = self.challenges.redeem(&request.into_inner())?;
+        Ok(Response::new(Redemption { hash }))
+    }
+
+    async fn get_chain(
+        &self,
+        request: Request<ChainRequest>,
+    ) -> Result<Response<BlockChain>, Status> {
+        let tenant = self.tenant(&request)?;
+        let blocks = self
+            .chain
+            .blocks()
+            .into_iter()
+            .map(|block| redact(tenant, block))
+            .collect();
+        Ok(Response::new(BlockChain { blocks }))
+    }
+
+    async fn get_block(&self, request: Request<BlockRequest>) -> Result<Response<Block>, Status> {
+        let tenant = self.tenant(&request)?;
+        match self.chain.get(&request.into_inner().hash) {
+            Some(block) => Ok(Response::new(redact(tenant, block))),
+            None => Err(Status::not_found("Block not found")),
+        }
+    }
+
+    async fn get_result(
+        &self,
+        request: Request<ResultRequest>,
+    ) -> Result<Response<BlockHash>, Status> {
+        let tenant = self.tenant(&request)?;
+        let ResultRequest { id, mut client } = request.into_inner();
+        if let (Some(tenant), true) = (tenant, client.is_empty()) {
+            client = tenant.name.clone();
+        }
+        if client.is_empty() {
+            return Err(Status::invalid_argument("Client name is required"));
+        }
+        check_client(tenant, &client)?;
+        if let Some(hash) = self.store.result(&client, &id).map_err(internal)? {
+            return Ok(Response::new(hash));
+        }
+        if let Some(status) = self.store.failure(&client, &id).map_err(internal)? {
+            return Err(status);
+        }
+        match self.store.job(&client, &id).map_err(internal)? {
+            Some(_) => Err(Status::failed_precondition("Block not mined")),
+            None => Err(Status::not_found("Block not found")),
+        }
+    }
+
+    async fn work(
+        &self,
+        request: Request<WorkerInfo>,
+    ) -> Result<Response<Self::WorkStream>, Status> {
+        check_worker(self.tenant(&request)?)?;
+        let WorkerInfo { name } = request.into_inner();
+        if name.is_empty() {
+            return Err(Status::invalid_argument("Worker name is required"));
+        }
+
+        let (tx, rx) = mpsc::channel(self.channel_size);
+        self.coordinator.register(name, tx);
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+
+    async fn report(&self, request: Request<WorkReport>) -> Result<Response<BlockStatus>, Status> {
+        check_worker(self.tenant(&request)?)?;
+        let report = request.into_inner();
+        let code = match self.coordinator.report(&report) {
+            true => 0,
+            false => 404,
+        };
+        Ok(Response::new(BlockStatus {
+            code,
+            job_id: report.job_id,
+        }))
+    }
+
+    async fn status(
+        &self,
+        _request: Request<StatusRequest>,
+    ) -> Result<Response<QueueStatus>, Status> {
+        Ok(Response::new(QueueStatus {
+            workers: self.coordinator.workers() as u32,
+            ..self.scheduler.status()
+        }))
+    }
+}
+
+impl PowService {
+    /// Serve the jobs of `scheduler`, delivering the events of `rx` to the subscribers and
+    /// recording the results in `store`. Once `rx` is closed, i.e. the scheduler is shut down,
+    /// the subscriber and worker streams end. The blocks mined by the scheduler are in `chain`.
+    pub fn new(
+        scheduler: Arc<Scheduler>,
+        mut rx: mpsc::Receiver<JobEvent>,
+        jobs: Arc<Jobs>,
+        coordinator: Arc<Coordinator>,
+        chain: Arc<Chain>,
+        store: Store,
+    ) -> Self {
+        let server = Self {
+            shared: Arc::new(RwLock::new(Shared::default())),
+            jobs,
+            coordinator,
+            scheduler,
+            challenges: ChallengeIssuer::new(
+                CHALLENGE_DIFFICULTY,
+                HashAlgorithm::Blake3,
+                CHALLENGE_TTL,
+            ),
+            chain,
+            store,
+            auth: None,
+            heartbeat: HEARTBEAT_INTERVAL,
+            channel_size: CHANNEL_SIZE,
+            difficulty: DEFAULT_DIFFICULTY,
+        };
+
+        let shared = server.shared.clone();
+        let coordinator = server.coordinator.clone();
+        let store = server.store.clone();
+
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let (client, kind) = match event {
+                    JobEvent::Progress { client, progress } => {
+                        (client, Ok(notification::Kind::Progress(progress)))
+                    }
+                    JobEvent::Finished(msg) => {
+                        let msg = *msg;
+                        let client = &msg.block.client;
+                        let stored = match &msg.result {
+                            Ok(hash) => store.add_result(client, hash),
+                            Err(status) => {
+                                store.add_failure(client, &get_block_id(&msg.block), status)
+                            }
+                        };
+                        if let Err(err) = stored {
+                            println!("Failed to store the result of job {}: {}", msg.job_id, err);
+                        }
+                        (
+                            msg.block.client,
+                            msg.result.map(notification::Kind::BlockHash),
+                        )
+                    }
+                };
+                let notification = kind.map(|kind| Notification { kind: Some(kind) });
+                shared.write().await.dispatch(&client, notification);
+            }
+            shared.write().await.close();
+            coordinator.close();
+            if let Err(err) = store.flush() {
+                println!("Failed to flush the store: {}", err);
+            }
+        });
+        server
+    }
+
+    pub fn with_heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat = interval;
+        self
+    }
+
+    /// Messages buffered by every subscriber and worker stream.
+    pub fn with_channel_size(mut self, size: usize) -> Self {
+        self.channel_size = size;
+        self
+    }
+
+    /// Difficulty of the blocks submitted without one.
+    pub fn with_difficulty(mut self, bits: u32) -> Self {
+        self.difficulty = bits.min(MAX_DIFFICULTY);
+        self
+    }
+
+    /// Only let the tenants of `auth` in, within their quotas.
+    pub fn with_auth(mut self, auth: Arc<Auth>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// The chain the mined blocks are appended to.
+    pub fn chain(&self) -> Arc<Chain> {
+        self.chain.clone()
+    }
+
+    // the tenant making the request, `None` if authentication is off
+    #[allow(clippy::result_large_err)]
+    fn tenant<T>(&self, request: &Request<T>) -> Result<Option<&Tenant>, Status> {
+        match &self.auth {
+            Some(auth) => auth.authenticate(request.metadata()).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// tenants act under their own name only
+#[allow(clippy::result_large_err)]
+fn check_client(tenant: Option<&Tenant>, client: &str) -> Result<(), Status> {
+    match tenant {
+        Some(tenant) if tenant.name != client => Err(Status::permission_denied(format!(
+            "{} can't act as client {}",
+            tenant.name, client
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// workers get the blocks of every tenant, and their results decide whether they are mined
+#[allow(clippy::result_large_err)]
+fn check_worker(tenant: Option<&Tenant>) -> Result<(), Status> {
+    match tenant {
+        Some(tenant) if !tenant.worker => Err(Status::permission_denied(format!(
+            "{} doesn't run workers",
+            tenant.name
+        ))),
+        _ => Ok(()),
+    }
+}
+
+// tenants only see the data of their own blocks in the chain
+fn redact(tenant: Option<&Tenant>, block: Block) -> Block {
+    match tenant {
+        Some(tenant) if tenant.name != block.client => Block {
+            data: vec![],
+            client: String::new(),
+            ..block
+        },
+        _ => block,
+    }
+}
+
+fn internal(err: anyhow::Error) -> Status {
+    Status::internal(format!("Store error: {}", err))
+}
@@ -0,0 +1,234 @@
+//! Jobs and results kept on disk, so that clients can get the results they missed.
+
+use std::path::Path;
+
+use anyhow::Result;
+use prost::Message;
+use tonic::{Code, Status};
+
+use crate::pb::{Block, BlockHash};
+use crate::pow::get_block_id;
+
+/// Submitted blocks and the results of the mined ones, or why they were not mined, keyed by
+/// client and block id: clients submitting the same data each get their own result. A client
+/// mining a block with the same data again replaces its result.
+#[derive(Debug, Clone)]
+pub struct Store {
+    db: sled::Db,
+    // client name length, client name, block id -> block as submitted
+    jobs: sled::Tree,
+    // client name length, client name, block id -> result
+    results: sled::Tree,
+    // client name length, client name, block id -> status code, message of the last attempt to
+    // mine it, if it failed
+    failures: sled::Tree,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_db(sled::open(path)?)
+    }
+
+    /// A store removed once dropped.
+    pub fn temporary() -> Result<Self> {
+        let db = sled::Config::new().temporary(true).open()?;
+        Self::with_db(db)
+    }
+
+    fn with_db(db: sled::Db) -> Result<Self> {
+        Ok(Self {
+            jobs: db.open_tree("jobs")?,
+            results: db.open_tree("results")?,
+            failures: db.open_tree("failures")?,
+            db,
+        })
+    }
+
+    pub fn add_job(&self, block: &Block) -> Result<()> {
+        let key = key(&block.client, &get_block_id(block));
+        self.jobs.insert(&key, encode(block))?;
+        // mined again
+        self.failures.remove(&key)?;
+        Ok(())
+    }
+
+    pub fn add_result(&self, client: &str, hash: &BlockHash) -> Result<()> {
+        let key = key(client, &hash.id);
+        self.results.insert(&key, encode(hash))?;
+        self.failures.remove(&key)?;
+        Ok(())
+    }
+
+    /// The block `id` of `client` was not mined, e.g. cancelled or out of nonces.
+    pub fn add_failure(&self, client: &str, id: &[u8], status: &Status) -> Result<()> {
+        let code = (status.code() as i32).to_be_bytes();
+        let value = [&code[..], status.message().as_bytes()].concat();
+        self.failures.insert(key(client, id), value)?;
+        Ok(())
+    }
+
+    /// The last block submitted by `client` with this id.
+    pub fn job(&self, client: &str, id: &[u8]) -> Result<Option<Block>> {
+        self.jobs.get(key(client, id))?.map(decode).transpose()
+    }
+
+    pub fn result(&self, client: &str, id: &[u8]) -> Result<Option<BlockHash>> {
+        self.results.get(key(client, id))?.map(decode).transpose()
+    }
+
+    /// Why the last attempt to mine the block `id` of `client` failed, if it did.
+    pub fn failure(&self, client: &str, id: &[u8]) -> Result<Option<Status>> {
+        let value = match self.failures.get(key(client, id))? {
+            Some(value) if value.len() >= 4 => value,
+            Some(_) => anyhow::bail!("Invalid failure of block {}", hex::encode(id)),
+            None => return Ok(None),
+        };
+        let (code, message) = value.split_at(4);
+        let code = i32::from_be_bytes(code.try_into()?);
+        let message = String::from_utf8_lossy(message);
+        Ok(Some(Status::new(Code::from_i32(code), message)))
+    }
+
+    /// Results of the blocks submitted by `client`, by block id, of every block if `None`, by
+    /// client then block id.
+    pub fn results(&self, client: Option<&str>) -> Result<Vec<BlockHash>> {
+        let prefix = client.map(client_prefix).unwrap_or_default();
+        self.results
+            .scan_prefix(prefix)
+            .values()
+            .map(|v| decode(v?))
+            .collect()
+    }
+
+    /// Write everything to disk, which otherwise happens in the background.
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+// names are length prefixed, so that no name is the prefix of another
+fn client_prefix(client: &str) -> Vec<u8> {
+    [&(client.len() as u32).to_be_bytes()[..], client.as_bytes()].concat()
+}
+
+fn key(client: &str, id: &[u8]) -> Vec<u8> {
+    [client_prefix(client), id.to_vec()].concat()
+}
+
+fn encode(msg: &impl Message) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(msg.encoded_len());
+    msg.encode(&mut buf).unwrap();
+    buf
+}
+
+fn decode<M: Message + Default>(value: sled::IVec) -> Result<M> {
+    Ok(M::decode(&*value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(data: &str, client: &str) -> Block {
+        Block {
+            data: data.as_bytes().to_vec(),
+            client: client.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn mined(block: &Block) -> BlockHash {
+        BlockHash {
+            id: get_block_id(block),
+            hash: vec![0; 32],
+            nonce: 42,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn results_should_survive_a_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = block("a", "alice");
+        {
+            let store = Store::open(dir.path()).unwrap();
+            store.add_job(&a).unwrap();
+            store.add_result("alice", &mined(&a)).unwrap();
+            store.add_job(&block("b", "alice")).unwrap();
+            store.flush().unwrap();
+        }
+
+        let store = Store::open(dir.path()).unwrap();
+        let id = get_block_id(&a);
+        assert_eq!(store.job("alice", &id).unwrap(), Some(a.clone()));
+        assert_eq!(store.result("alice", &id).unwrap(), Some(mined(&a)));
+        // b was never mined
+        let b = get_block_id(&block("b", "alice"));
+        assert!(store.job("alice", &b).unwrap().is_some());
+        assert_eq!(store.result("alice", &b).unwrap(), None);
+    }
+
+    #[test]
+    fn results_should_be_listed_by_client() {
+        let store = Store::temporary().unwrap();
+        let blocks = [block("a", "alice"), block("b", "alice"), block("c", "al")];
+        for block in &blocks {
+            store.add_job(block).unwrap();
+            store.add_result(&block.client, &mined(block)).unwrap();
+        }
+        store.add_job(&block("d", "alice")).unwrap();
+
+        let alice = store.results(Some("alice")).unwrap();
+        let mut expected = vec![mined(&blocks[0]), mined(&blocks[1])];
+        expected.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(alice, expected);
+        assert_eq!(store.results(Some("al")).unwrap(), vec![mined(&blocks[2])]);
+        assert_eq!(store.results(Some("bob")).unwrap(), vec![]);
+        assert_eq!(store.results(None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn failures_should_be_cleared_once_mined() {
+        let store = Store::temporary().unwrap();
+        let a = block("a", "alice");
+        let id = get_block_id(&a);
+        store.add_job(&a).unwrap();
+        assert!(store.failure("alice", &id).unwrap().is_none());
+
+        let cancelled = Status::cancelled("Job 1 cancelled");
+        store.add_failure("alice", &id, &cancelled).unwrap();
+        let failure = store.failure("alice", &id).unwrap().unwrap();
+        assert_eq!(failure.code(), Code::Cancelled);
+        assert_eq!(failure.message(), "Job 1 cancelled");
+
+        // submitted again, then mined
+        store.add_job(&a).unwrap();
+        assert!(store.failure("alice", &id).unwrap().is_none());
+        store.add_failure("alice", &id, &cancelled).unwrap();
+        store.add_result("alice", &mined(&a)).unwrap();
+        assert!(store.failure("alice", &id).unwrap().is_none());
+    }
+
+    #[test]
+    fn clients_should_not_share_the_blocks_with_the_same_data() {
+        let store = Store::temporary().unwrap();
+        let (a, b) = (block("data", "alice"), block("data", "bob"));
+        let id = get_block_id(&a);
+        assert_eq!(id, get_block_id(&b));
+        store.add_job(&a).unwrap();
+        store.add_result("alice", &mined(&a)).unwrap();
+        store.add_job(&b).unwrap();
+        store
+            .add_failure("bob", &id, &Status::cancelled("Job 2 cancelled"))
+            .unwrap();
+
+        assert_eq!(store.job("alice", &id).unwrap(), Some(a.clone()));
+        assert_eq!(store.job("bob", &id).unwrap(), Some(b));
+        assert_eq!(store.result("alice", &id).unwrap(), Some(mined(&a)));
+        assert_eq!(store.result("bob", &id).unwrap(), None);
+        assert!(store.failure("alice", &id).unwrap().is_none());
+        assert!(store.failure("bob", &id).unwrap().is_some());
+        assert_eq!(store.results(Some("bob")).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,371 @@
+//! Stratum-like mining pool: line-delimited JSON-RPC over TCP.
+//!
+//! A miner sends `mining.subscribe` to get its extra nonce, `mining.authorize` for every worker
+//! name it mines under, then `mining.submit` with `[worker, job id, nonce]` for every share it
+//! finds. When the pool has tenants, workers are authorized with `[tenant name, token]`, or
+//! `[tenant name.rig, token]`, if their tenant runs workers. The pool sends
+//! `mining.set_difficulty` with the share difficulty, and `mining.notify` with `[job id, data,
+//! previous hash, height, timestamp, algorithm, block difficulty, clean]` every time there is a
+//! new block to mine, all binary fields hex encoded.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_util::codec::{FramedRead, LinesCodec};
+
+use crate::auth::Auth;
+use crate::chain::Chain;
+use crate::pb::Block;
+use crate::pow::{difficulty, leading_zero_bits, pow_hash};
+
+// how often the pool checks whether the chain moved under its job
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+const POOL_DATA: &[u8] = b"rust-usage pool";
+/// Longest request a miner can send, the connection is closed past it.
+pub const MAX_LINE_LENGTH: usize = 4096;
+
+/// Shares and blocks found by a worker.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub blocks: u64,
+}
+
+/// Stratum errors, sent as `[code, message, null]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StratumError {
+    Other = 20,
+    JobNotFound = 21,
+    DuplicateShare = 22,
+    LowDifficultyShare = 23,
+    UnauthorizedWorker = 24,
+    NotSubscribed = 25,
+}
+
+impl StratumError {
+    fn to_json(self) -> Value {
+        let message = match self {
+            StratumError::Other => "Other/Unknown",
+            StratumError::JobNotFound => "Job not found",
+            StratumError::DuplicateShare => "Duplicate share",
+            StratumError::LowDifficultyShare => "Low difficulty share",
+            StratumError::UnauthorizedWorker => "Unauthorized worker",
+            StratumError::NotSubscribed => "Not subscribed",
+        };
+        json!([self as u32, message, null])
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Vec<Value>,
+}
+
+#[derive(Default)]
+struct State {
+    next_job_id: u64,
+    // id of the job shares are accepted for, with the block mined, the previous jobs are stale
+    current: Option<(String, Block)>,
+    // (extra nonce, nonce) of the shares accepted for the current job
+    shares: HashSet<(u64, u64)>,
+    accounts: HashMap<String, Account>,
+}
+
+/// Blocks of the chain mined by miners pooling their work. Every share has at least
+/// `share_difficulty` leading zero bits, the shares with `block_difficulty` bits also mine the
+/// block.
+pub struct Pool {
+    chain: Arc<Chain>,
+    share_difficulty: u32,
+    block_difficulty: u32,
+    next_extra_nonce: AtomicU64,
+    state: Mutex<State>,
+    // `mining.notify` of the new jobs
+    jobs: broadcast::Sender<Value>,
+    // tenants allowed to mine, anyone if `None`
+    auth: Option<Arc<Auth>>,
+}
+
+impl Pool {
+    pub fn new(chain: Arc<Chain>, share_difficulty: u32, block_difficulty: u32) -> Self {
+        let (jobs, _) = broadcast::channel(16);
+        Self {
+            chain,
+            share_difficulty: share_difficulty.min(block_difficulty),
+            block_difficulty,
+            next_extra_nonce: AtomicU64::new(0),
+            state: Mutex::new(State::default()),
+            jobs,
+            auth: None,
+        }
+    }
+
+    /// Only authorize the workers of the tenants of `auth` running workers.
+    pub fn with_auth(mut self, auth: Arc<Auth>) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    // whether `password` is the token of the tenant `worker` belongs to, and it runs workers
+    fn authorize(&self, worker: &str, password: &str) -> bool {
+        let auth = match &self.auth {
+            Some(auth) => auth,
+            None => return true,
+        };
+        let name = worker.split('.').next().unwrap_or_default();
+        matches!(auth.tenant(password), Some(tenant) if tenant.name == name && tenant.worker)
+    }
+
+    pub fn accounts(&self) -> HashMap<String, Account> {
+        self.state.lock().unwrap().accounts.clone()
+    }
+
+    /// `mining.notify` of the current job, starting a new one if the chain moved.
+    fn job(&self) -> Value {
+        let mut state = self.state.lock().unwrap();
+        self.refresh(&mut state);
+        let (id, block) = state.current.as_ref().unwrap();
+        notify(id, block, false)
+    }
+
+    // start a new job on top of the tip if there is none yet or if the tip changed, and tell
+    // every miner to drop the previous ones
+    fn refresh(&self, state: &mut State) {
+        let tip = self.chain.tip().map(|tip| tip.hash).unwrap_or_default();
+        if matches!(&state.current, Some((_, block)) if block.prev_hash == tip) {
+            return;
+        }
+
+        state.next_job_id += 1;
+        let id = format!("{:x}", state.next_job_id);
+        let block = self.chain.link(Block {
+            data: POOL_DATA.to_vec(),
+            difficulty: self.block_difficulty,
+            ..Default::default()
+        });
+        state.shares.clear();
+        let _ = self.jobs.send(notify(&id, &block, true));
+        state.current = Some((id, block));
+    }
+
+    /// Check a share, appending the block to the chain if it meets the block difficulty.
+    fn submit(
+        &self,
+        worker: &str,
+        job_id: &str,
+        extra_nonce: u64,
+        nonce: u64,
+    ) -> Result<(), StratumError> {
+        let mut state = self.state.lock().unwrap();
+        let result = self.check(&mut state, job_id, extra_nonce, nonce);
+        let account = state.accounts.entry(worker.to_string()).or_default();
+        match result {
+            Ok(block) => {
+                account.accepted += 1;
+                if let Some(block) = block {
+                    match self.chain.append(block) {
+                        Ok(_) => account.blocks += 1,
+                        Err(err) => println!("Failed to append the pool block: {}", err),
+                    }
+                    self.refresh(&mut state);
+                }
+                Ok(())
+            }
+            Err(err) => {
+                account.rejected += 1;
+                Err(err)
+            }
+        }
+    }
+
+    // the mined block if the share is valid and meets the block difficulty
+    fn check(
+        &self,
+        state: &mut State,
+        job_id: &str,
+        extra_nonce: u64,
+        nonce: u64,
+    ) -> Result<Option<Block>, StratumError> {
+        let block = match &state.current {
+            Some((id, block)) if id == job_id => Block {
+                extra_nonce,
+                ..block.clone()
+            },
+            _ => return Err(StratumError::JobNotFound),
+        };
+        let hash = pow_hash(&block, nonce);
+        let bits = leading_zero_bits(&hash);
+        if bits < self.share_difficulty {
+            return Err(StratumError::LowDifficultyShare);
+        }
+        if !state.shares.insert((extra_nonce, nonce)) {
+            return Err(StratumError::DuplicateShare);
+        }
+
+        match bits >= difficulty(&block) {
+            true => Ok(Some(Block {
+                hash,
+                nonce,
+                ..block
+            })),
+            false => Ok(None),
+        }
+    }
+}
+
+fn notify(id: &str, block: &Block, clean: bool) -> Value {
+    json!({
+        "id": null,
+        "method": "mining.notify",
+        "params": [
+            id,
+            hex::encode(&block.data),
+            hex::encode(&block.prev_hash),
+            block.height,
+            block.timestamp,
+            block.algorithm,
+            block.difficulty,
+            clean,
+        ],
+    })
+}
+
+/// Accept miners on `listener` until it fails.
+pub async fn serve(listener: TcpListener, pool: Arc<Pool>) -> Result<()> {
+    let refresh = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            refresh.refresh(&mut refresh.state.lock().unwrap());
+        }
+    });
+
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, pool).await {
+                println!("Stratum connection {} failed: {}", addr, err);
+            }
+        });
+    }
+}
+
+#[derive(Default)]
+struct Session {
+    // the share of the nonce space of the miner, set on subscription
+    extra_nonce: Option<u64>,
+    workers: HashSet<String>,
+    // id of the last job notified
+    job_id: Value,
+}
+
+async fn handle(stream: TcpStream, pool: Arc<Pool>) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = FramedRead::new(reader, LinesCodec::new_with_max_length(MAX_LINE_LENGTH));
+    let mut jobs = pool.jobs.subscribe();
+    let mut session = Session::default();
+
+    loop {
+        let messages = tokio::select! {
+            line = lines.next() => match line.transpose()? {
+                Some(line) => session.call(&pool, &line),
+                None => return Ok(()),
+            },
+            job = jobs.recv() => match (job, session.extra_nonce) {
+                (Ok(job), Some(_)) => vec![job],
+                // a miner missing jobs gets the current one
+                (Err(broadcast::error::RecvError::Lagged(_)), Some(_)) => vec![pool.job()],
+                (Err(err), _) => return Err(err.into()),
+                (Ok(_), None) => vec![],
+            },
+        };
+        for message in messages {
+            // a job can be both broadcast and sent on subscription
+            if message["method"] == "mining.notify" {
+                if message["params"][0] == session.job_id {
+                    continue;
+                }
+                session.job_id = message["params"][0].clone();
+            }
+            writer
+                .write_all(format!("{}\n", message).as_bytes())
+                .await?;
+        }
+    }
+}
+
+impl Session {
+    // the response to the request, followed by the notifications it triggers
+    fn call(&mut self, pool: &Pool, line: &str) -> Vec<Value> {
+        let request: Request = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(_) => return vec![response(Value::Null, Err(StratumError::Other))],
+        };
+        let param = |i: usize| request.params.get(i).and_then(Value::as_str).unwrap_or("");
+
+        match request.method.as_str() {
+            "mining.subscribe" => {
+                let extra_nonce = *self
+                    .extra_nonce
+                    .get_or_insert_with(|| pool.next_extra_nonce.fetch_add(1, Ordering::Relaxed));
+                let subscriptions = json!([["mining.set_difficulty", "1"], ["mining.notify", "1"]]);
+                vec![
+                    response(request.id, Ok(json!([subscriptions, extra_nonce]))),
+                    json!({
+                        "id": null,
+                        "method": "mining.set_difficulty",
+                        "params": [pool.share_difficulty],
+                    }),
+                    pool.job(),
+                ]
+            }
+            "mining.authorize" => {
+                let result = match pool.authorize(param(0), param(1)) {
+                    true => {
+                        self.workers.insert(param(0).to_string());
+                        Ok(json!(true))
+                    }
+                    false => Err(StratumError::UnauthorizedWorker),
+                };
+                vec![response(request.id, result)]
+            }
+            "mining.submit" => {
+                let result = match (self.extra_nonce, param(0)) {
+                    (None, _) => Err(StratumError::NotSubscribed),
+                    (_, worker) if !self.workers.contains(worker) => {
+                        Err(StratumError::UnauthorizedWorker)
+                    }
+                    (Some(extra_nonce), worker) => match param(2).parse() {
+                        Ok(nonce) => pool.submit(worker, param(1), extra_nonce, nonce),
+                        Err(_) => Err(StratumError::Other),
+                    },
+                };
+                vec![response(request.id, result.map(|_| json!(true)))]
+            }
+            _ => vec![response(request.id, Err(StratumError::Other))],
+        }
+    }
+}
+
+fn response(id: Value, result: Result<Value, StratumError>) -> Value {
+    match result {
+        Ok(result) => json!({ "id": id, "result": result, "error": null }),
+        Err(err) => json!({ "id": id, "result": null, "error": err.to_json() }),
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use tonic::transport::Channel;
+
+use crate::pb::pow_builder_client::PowBuilderClient;
+use crate::pb::{WorkReport, WorkUnit, WorkerInfo};
+use crate::pow::pow_range;
+
+/// Register as `name` and search the ranges the server hands out until it closes the stream.
+pub async fn work(mut client: PowBuilderClient<Channel>, name: String) -> Result<()> {
+    let mut stream = client
+        .work(WorkerInfo { name: name.clone() })
+        .await?
+        .into_inner();
+
+    // job being searched, with its stop flag
+    let mut current: Option<(u64, Arc<AtomicBool>)> = None;
+    while let Some(unit) = stream.message().await? {
+        if let Some((job_id, stop)) = &current {
+            if *job_id == unit.job_id && unit.stop {
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+        if unit.stop {
+            continue;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        current = Some((unit.job_id, stop.clone()));
+        let mut client = client.clone();
+        let name = name.clone();
+        tokio::spawn(async move {
+            let job_id = unit.job_id;
+            if let Some(report) = search(name, unit, stop).await {
+                if let Err(err) = client.report(report).await {
+                    println!("Failed to report job {}: {}", job_id, err);
+                }
+            }
+        });
+    }
+
+    Ok(())
+}
+
+// the report of the range, none if the search was stopped
+async fn search(worker: String, unit: WorkUnit, stop: Arc<AtomicBool>) -> Option<WorkReport> {
+    let block = unit.block.unwrap_or_default();
+    let nonces = unit.nonce_start..unit.nonce_end;
+    let cancel = stop.clone();
+    let found = tokio::task::spawn_blocking(move || pow_range(block, nonces, &cancel))
+        .await
+        .ok()?;
+    if stop.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    Some(WorkReport {
+        worker,
+        job_id: unit.job_id,
+        nonce_start: unit.nonce_start,
+        found: found.is_some(),
+        nonce: found.map(|hash| hash.nonce).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,290 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use pow::auth::{connect, Auth, Tenant};
+use pow::pb::health::health_client::HealthClient;
+use pow::pb::health::HealthCheckRequest;
+use pow::pb::pow_builder_client::PowBuilderClient;
+use pow::pb::{
+    notification, Block, BlockHash, BlockRequest, ChainRequest, ClientInfo, JobInfo, ResultRequest,
+    StatusRequest, WorkReport, WorkerInfo,
+};
+use pow::server::{Config, PowServer};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Channel;
+use tonic::Code;
+
+async fn start_server(config: Config) -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let tenants = vec![
+        Tenant {
+            name: "alice".to_string(),
+            token: "alice-token".to_string(),
+            jobs_per_minute: Some(2),
+            max_difficulty: Some(64),
+            worker: false,
+        },
+        Tenant {
+            name: "bob".to_string(),
+            token: "bob-token".to_string(),
+            jobs_per_minute: None,
+            max_difficulty: None,
+            worker: false,
+        },
+        Tenant {
+            name: "carol".to_string(),
+            token: "carol-token".to_string(),
+            jobs_per_minute: None,
+            max_difficulty: None,
+            worker: true,
+        },
+    ];
+    let server = PowServer::new(&Config {
+        auth: Some(Arc::new(Auth::new(tenants))),
+        ..config
+    })?;
+    tokio::spawn(server.serve(TcpListenerStream::new(listener), futures::future::pending()));
+    Ok(addr)
+}
+
+fn block(client: &str, difficulty: u32) -> Block {
+    Block {
+        data: b"hello world".to_vec(),
+        difficulty,
+        client: client.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn clients_without_a_valid_token_should_be_rejected() -> Result<()> {
+    let addr = format!("http://{}", start_server(Config::default()).await?);
+
+    for token in [None, Some("wrong")] {
+        let mut client = connect(addr.clone(), token).await?;
+        let err = client.status(StatusRequest {}).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+        let err = client.submit(block("alice", 8)).await.unwrap_err();
+        assert_eq!(err.code(), Code::Unauthenticated);
+    }
+
+    // health checks are open
+    let mut health = HealthClient::connect(addr.clone()).await?;
+    let request = HealthCheckRequest {
+        service: String::new(),
+    };
+    health.check(request).await?;
+
+    let mut client = connect(addr, Some("alice-token")).await?;
+    client.status(StatusRequest {}).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tenants_should_act_under_their_own_name() -> Result<()> {
+    let addr = format!("http://{}", start_server(Config::default()).await?);
+    let mut alice = connect(addr.clone(), Some("alice-token")).await?;
+    let mut bob = connect(addr, Some("bob-token")).await?;
+
+    let subscribe = |name: &str, broadcast| ClientInfo {
+        name: name.to_string(),
+        broadcast,
+        ..Default::default()
+    };
+    let err = alice.subscribe(subscribe("bob", false)).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    let err = alice.subscribe(subscribe("alice", true)).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    alice.subscribe(subscribe("alice", false)).await?;
+
+    let err = alice.submit(block("bob", 8)).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    // the client name defaults to the tenant
+    let job_id = bob.submit(block("", 64)).await?.into_inner().job_id;
+    let err = alice.cancel(JobInfo { job_id }).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    assert_eq!(bob.cancel(JobInfo { job_id }).await?.into_inner().code, 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tenants_should_stay_within_their_quotas() -> Result<()> {
+    let addr = format!("http://{}", start_server(Config::default()).await?);
+    let mut alice = connect(addr.clone(), Some("alice-token")).await?;
+    let mut bob = connect(addr, Some("bob-token")).await?;
+
+    let err = alice.submit(block("alice", 65)).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    for _ in 0..2 {
+        let job_id = alice.submit(block("alice", 64)).await?.into_inner().job_id;
+        alice.cancel(JobInfo { job_id }).await?;
+    }
+    let err = alice.submit(block("alice", 64)).await.unwrap_err();
+    assert_eq!(err.code(), Code::ResourceExhausted);
+
+    // bob has no quotas
+    for _ in 0..3 {
+        let job_id = bob.submit(block("bob", 128)).await?.into_inner().job_id;
+        bob.cancel(JobInfo { job_id }).await?;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_blocks_should_not_count_against_the_quotas() -> Result<()> {
+    // a single job runs at a time, another one waits in the queue
+    let config = Config {
+        concurrent_jobs: 1,
+        queue_size: 1,
+        ..Default::default()
+    };
+    let addr = format!("http://{}", start_server(config).await?);
+    let mut alice = connect(addr.clone(), Some("alice-token")).await?;
+    let mut bob = connect(addr, Some("bob-token")).await?;
+
+    let mut blockers = Vec::new();
+    for _ in 0..2 {
+        blockers.push(bob.submit(block("bob", 128)).await?.into_inner().job_id);
+        wait_for(&mut bob, |running, queued| {
+            running + queued == blockers.len() as u32
+        })
+        .await?;
+    }
+    for _ in 0..3 {
+        let err = alice.submit(block("alice", 8)).await.unwrap_err();
+        assert_eq!(err.code(), Code::ResourceExhausted);
+    }
+
+    for job_id in blockers {
+        bob.cancel(JobInfo { job_id }).await?;
+    }
+    wait_for(&mut bob, |running, queued| running + queued == 0).await?;
+    for _ in 0..2 {
+        let job_id = alice.submit(block("alice", 64)).await?.into_inner().job_id;
+        alice.cancel(JobInfo { job_id }).await?;
+        wait_for(&mut alice, |running, queued| running + queued == 0).await?;
+    }
+    let err = alice.submit(block("alice", 8)).await.unwrap_err();
+    assert_eq!(err.code(), Code::ResourceExhausted);
+    Ok(())
+}
+
+#[tokio::test]
+async fn tenants_should_only_see_their_own_blocks() -> Result<()> {
+    let addr = format!("http://{}", start_server(Config::default()).await?);
+    let mut alice = connect(addr.clone(), Some("alice-token")).await?;
+    let mut bob = connect(addr.clone(), Some("bob-token")).await?;
+
+    bob.submit(block("bob", 8)).await?;
+    // the client defaults to the tenant
+    let request = || ResultRequest {
+        id: blake3::hash(b"hello world").as_bytes().to_vec(),
+        ..Default::default()
+    };
+    let hash = wait_for_result(&mut bob, request()).await?;
+    let err = alice.get_result(request()).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let of_bob = ResultRequest {
+        client: "bob".to_string(),
+        ..request()
+    };
+    let err = alice.get_result(of_bob).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+
+    // the chain is shared, but not the data of the blocks of others
+    let chain = bob.get_chain(ChainRequest {}).await?.into_inner().blocks;
+    assert_eq!(chain[0].data, b"hello world");
+    let chain = alice.get_chain(ChainRequest {}).await?.into_inner().blocks;
+    assert_eq!(
+        (chain[0].data.is_empty(), chain[0].client.is_empty()),
+        (true, true)
+    );
+    assert_eq!(chain[0].hash, hash.hash);
+    let request = BlockRequest { hash: hash.hash };
+    let block = alice.get_block(request).await?.into_inner();
+    assert!(block.data.is_empty());
+
+    // only tenants running workers mine the blocks of others
+    let worker = || WorkerInfo {
+        name: "rig".to_string(),
+    };
+    let err = alice.work(worker()).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    let err = alice.report(WorkReport::default()).await.unwrap_err();
+    assert_eq!(err.code(), Code::PermissionDenied);
+    let mut carol = connect(addr, Some("carol-token")).await?;
+    carol.work(worker()).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn tenants_should_get_their_own_result_of_the_same_data() -> Result<()> {
+    let addr = format!("http://{}", start_server(Config::default()).await?);
+    let mut alice = connect(addr.clone(), Some("alice-token")).await?;
+    let mut bob = connect(addr, Some("bob-token")).await?;
+    let request = || ResultRequest {
+        id: blake3::hash(b"hello world").as_bytes().to_vec(),
+        ..Default::default()
+    };
+
+    let of_bob = bob.submit(block("bob", 8)).await?.into_inner().job_id;
+    assert_eq!(wait_for_result(&mut bob, request()).await?.job_id, of_bob);
+    let of_alice = alice.submit(block("alice", 8)).await?.into_inner().job_id;
+    assert_eq!(
+        wait_for_result(&mut alice, request()).await?.job_id,
+        of_alice
+    );
+    assert_eq!(bob.get_result(request()).await?.into_inner().job_id, of_bob);
+
+    // replays only have the results of the subscriber
+    for (client, name, job_id) in [(&mut alice, "alice", of_alice), (&mut bob, "bob", of_bob)] {
+        let info = ClientInfo {
+            name: name.to_string(),
+            replay: true,
+            ..Default::default()
+        };
+        let mut stream = client.subscribe(info).await?.into_inner();
+        match stream.message().await?.and_then(|n| n.kind) {
+            Some(notification::Kind::BlockHash(hash)) => assert_eq!(hash.job_id, job_id),
+            kind => panic!("unexpected notification {:?}", kind),
+        }
+    }
+    Ok(())
+}
+
+// the result of the block of `request`, once mined
+async fn wait_for_result(
+    client: &mut PowBuilderClient<Channel>,
+    request: ResultRequest,
+) -> Result<BlockHash> {
+    let mut retries = 0;
+    loop {
+        match client.get_result(request.clone()).await {
+            Ok(hash) => return Ok(hash.into_inner()),
+            Err(err) => assert_eq!(err.code(), Code::FailedPrecondition),
+        }
+        assert!(retries < 500, "the block was never mined");
+        retries += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+}
+
+async fn wait_for(
+    client: &mut PowBuilderClient<Channel>,
+    done: impl Fn(u32, u32) -> bool,
+) -> Result<()> {
+    let mut retries = 0;
+    loop {
+        let status = client.status(StatusRequest {}).await?.into_inner();
+        if done(status.running, status.queued) {
+            return Ok(());
+        }
+        assert!(retries < 500, "the queue never got there");
+        retries += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+}
@@ -0,0 +1,33 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::pow_builder_client::PowBuilderClient;
+use pow::server::{Config, PowServer};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Channel;
+
+/// Serve on an ephemeral port until the test ends.
+pub async fn start_server(heartbeat: Duration) -> Result<SocketAddr> {
+    serve(&Config {
+        queue_size: 8,
+        heartbeat,
+        ..Default::default()
+    })
+    .await
+}
+
+/// Serve with `config` on an ephemeral port until the test ends.
+pub async fn serve(config: &Config) -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    let server = PowServer::new(config)?;
+    tokio::spawn(server.serve(TcpListenerStream::new(listener), futures::future::pending()));
+    Ok(addr)
+}
+
+pub async fn connect(addr: SocketAddr) -> Result<PowBuilderClient<Channel>> {
+    Ok(PowBuilderClient::connect(format!("http://{}", addr)).await?)
+}
@@ -0,0 +1,159 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::{
+    notification, Block, BlockHash, BlockRequest, ChainRequest, ClientInfo, HashAlgorithm,
+    Notification,
+};
+use pow::pow::DEFAULT_DIFFICULTY;
+use pow::server::{Config, PowServer};
+use pow::service::HEARTBEAT_INTERVAL;
+use pow::{pow_hash, verify};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::Streaming;
+
+mod common;
+use common::{connect, start_server};
+
+// the result of the next job, skipping the other notifications
+async fn next_hash(stream: &mut Streaming<Notification>) -> Result<BlockHash> {
+    loop {
+        match stream.message().await?.and_then(|n| n.kind) {
+            Some(notification::Kind::BlockHash(hash)) => return Ok(hash),
+            Some(_) => continue,
+            None => anyhow::bail!("stream ended without a result"),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn submitted_blocks_should_be_mined_and_verify() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client = connect(addr).await?;
+    let mut stream = client
+        .subscribe(ClientInfo {
+            name: "alice".to_string(),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+
+    let algorithms = [HashAlgorithm::Blake3, HashAlgorithm::Sha256d];
+    for (i, algorithm) in algorithms.into_iter().enumerate() {
+        let data = format!("block {}", i).into_bytes();
+        let block = Block {
+            data: data.clone(),
+            difficulty: 12,
+            algorithm: algorithm as i32,
+            client: "alice".to_string(),
+            ..Default::default()
+        };
+        let job_id = client.submit(block).await?.into_inner().job_id;
+
+        let hash = next_hash(&mut stream).await?;
+        assert_eq!(hash.job_id, job_id);
+        assert_eq!(hash.id, blake3::hash(&data).as_bytes().to_vec());
+        assert_eq!(hash.difficulty, 12);
+        assert_eq!(hash.algorithm, algorithm as i32);
+
+        // the block was linked to the chain before being mined
+        let block = client
+            .get_block(BlockRequest {
+                hash: hash.hash.clone(),
+            })
+            .await?
+            .into_inner();
+        assert_eq!(block.height, i as u64);
+        assert_eq!(
+            (block.nonce, block.extra_nonce),
+            (hash.nonce, hash.extra_nonce)
+        );
+        assert_eq!(pow_hash(&block, hash.nonce), hash.hash);
+        assert!(verify(&block, hash.nonce, hash.difficulty));
+        assert!(client.verify(block).await?.into_inner().valid);
+    }
+
+    let chain = client.get_chain(ChainRequest {}).await?.into_inner();
+    assert_eq!(chain.blocks.len(), algorithms.len());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn blocks_without_difficulty_should_get_the_configured_one() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = PowServer::new(&Config {
+        difficulty: 8,
+        ..Default::default()
+    })?;
+    tokio::spawn(server.serve(TcpListenerStream::new(listener), futures::future::pending()));
+
+    let mut client = connect(addr).await?;
+    let mut stream = client
+        .subscribe(ClientInfo {
+            name: "alice".to_string(),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    let block = Block {
+        data: b"hello world".to_vec(),
+        client: "alice".to_string(),
+        ..Default::default()
+    };
+    client.submit(block).await?;
+
+    let hash = tokio::time::timeout(Duration::from_secs(10), next_hash(&mut stream)).await??;
+    assert_ne!(DEFAULT_DIFFICULTY, 8);
+    assert_eq!(hash.difficulty, 8);
+    let block = client
+        .get_block(BlockRequest { hash: hash.hash })
+        .await?
+        .into_inner();
+    assert_eq!(block.difficulty, 8);
+    assert!(verify(&block, block.nonce, 8));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn blocks_mined_concurrently_should_all_be_in_the_chain() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client = connect(addr).await?;
+    let mut stream = client
+        .subscribe(ClientInfo {
+            name: "alice".to_string(),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+
+    // more blocks than jobs running at the same time, all linked to the same tip at first
+    let data: Vec<_> = (0..6)
+        .map(|i| format!("block {}", i).into_bytes())
+        .collect();
+    let submissions = data.iter().map(|data| {
+        let mut client = client.clone();
+        let block = Block {
+            data: data.clone(),
+            difficulty: 10,
+            client: "alice".to_string(),
+            ..Default::default()
+        };
+        async move { client.submit(block).await }
+    });
+    for status in futures::future::join_all(submissions).await {
+        status?;
+    }
+    for _ in &data {
+        next_hash(&mut stream).await?;
+    }
+
+    let chain = client.get_chain(ChainRequest {}).await?.into_inner();
+    let heights: Vec<_> = chain.blocks.iter().map(|block| block.height).collect();
+    assert_eq!(heights, (0..data.len() as u64).collect::<Vec<_>>());
+    let mined: HashSet<_> = chain.blocks.into_iter().map(|block| block.data).collect();
+    assert_eq!(mined, data.into_iter().collect());
+    Ok(())
+}
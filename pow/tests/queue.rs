@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::pow_builder_client::PowBuilderClient;
+use pow::pb::{notification, Block, ClientInfo, JobInfo, ResultRequest, StatusRequest};
+use pow::server::{Config, PowServer};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::Code;
+
+fn block(data: &str, difficulty: u32, priority: u32) -> Block {
+    Block {
+        data: data.as_bytes().to_vec(),
+        difficulty,
+        priority,
+        client: "alice".to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn queued_jobs_should_run_by_priority_until_the_queue_is_full() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    // a single job runs at a time, two wait in the queue
+    let server = PowServer::new(&Config {
+        concurrent_jobs: 1,
+        queue_size: 2,
+        ..Default::default()
+    })?;
+    tokio::spawn(server.serve(TcpListenerStream::new(listener), futures::future::pending()));
+
+    let mut client = PowBuilderClient::connect(format!("http://{}", addr)).await?;
+    // results of the blocks of bob are not delivered to anyone
+    let blocker = Block {
+        client: "bob".to_string(),
+        ..block("blocker", 64, 0)
+    };
+    let blocker = client.submit(blocker).await?.into_inner().job_id;
+    let mut retries = 0;
+    while client.status(StatusRequest {}).await?.into_inner().running == 0 {
+        assert!(retries < 100, "the first job never started");
+        retries += 1;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut stream = client
+        .subscribe(ClientInfo {
+            name: "alice".to_string(),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    let low = client.submit(block("low", 8, 0)).await?.into_inner().job_id;
+    let high = client
+        .submit(block("high", 8, 10))
+        .await?
+        .into_inner()
+        .job_id;
+
+    // back-pressure once the queue is full
+    let err = client.submit(block("more", 8, 20)).await.unwrap_err();
+    assert_eq!(err.code(), Code::ResourceExhausted);
+    // and the block rejected is not kept
+    let more = ResultRequest {
+        id: blake3::hash(b"more").as_bytes().to_vec(),
+        client: "alice".to_string(),
+    };
+    let err = client.get_result(more).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+    let status = client.status(StatusRequest {}).await?.into_inner();
+    assert_eq!((status.running, status.queued), (1, 2));
+
+    // the job queued last but with a higher priority runs first
+    client.cancel(JobInfo { job_id: blocker }).await?;
+    let mut mined = Vec::new();
+    while mined.len() < 2 {
+        match stream.message().await?.and_then(|n| n.kind) {
+            Some(notification::Kind::BlockHash(hash)) => mined.push(hash.job_id),
+            Some(_) => continue,
+            None => anyhow::bail!("stream ended without a result"),
+        }
+    }
+    assert_eq!(mined, vec![high, low]);
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::pow_builder_client::PowBuilderClient;
+use pow::pb::{notification, Block, BlockHash, ClientInfo, JobInfo, ResultRequest};
+use pow::server::Config;
+use pow::service::HEARTBEAT_INTERVAL;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Code;
+
+mod common;
+use common::{connect, serve, start_server};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn results_should_be_kept_for_late_subscribers() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client = connect(addr).await?;
+
+    // nobody is subscribed when the block is mined
+    let block = Block {
+        data: b"hello world".to_vec(),
+        difficulty: 12,
+        client: "alice".to_string(),
+        ..Default::default()
+    };
+    let job_id = client.submit(block.clone()).await?.into_inner().job_id;
+    let id = blake3::hash(&block.data).as_bytes().to_vec();
+    let request = || ResultRequest {
+        id: id.clone(),
+        client: "alice".to_string(),
+    };
+    let mut retries = 0;
+    let hash = loop {
+        match client.get_result(request()).await {
+            Ok(hash) => break hash.into_inner(),
+            Err(err) => assert_eq!(err.code(), Code::FailedPrecondition),
+        }
+        assert!(retries < 500, "the block was never mined");
+        retries += 1;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    assert_eq!(hash.job_id, job_id);
+    assert_eq!(hash.id, id);
+    assert_eq!(hash.difficulty, 12);
+
+    // ids are scoped to their client
+    for (id, name) in [(vec![0; 32], "alice"), (id.clone(), "bob")] {
+        let request = ResultRequest {
+            id,
+            client: name.to_string(),
+        };
+        let err = client.get_result(request).await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+    }
+
+    // the result is replayed to its client, and to broadcast subscribers
+    for (name, broadcast) in [("alice", false), ("bob", true)] {
+        let mut stream = client
+            .subscribe(ClientInfo {
+                name: name.to_string(),
+                broadcast,
+                replay: true,
+            })
+            .await?
+            .into_inner();
+        assert_eq!(next_hash(stream.message().await?.unwrap().kind), hash);
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failures_should_be_kept_until_mined() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client = connect(addr).await?;
+
+    let block = Block {
+        data: b"never mined".to_vec(),
+        difficulty: 64,
+        client: "alice".to_string(),
+        ..Default::default()
+    };
+    let job_id = client.submit(block.clone()).await?.into_inner().job_id;
+    let request = ResultRequest {
+        id: blake3::hash(&block.data).as_bytes().to_vec(),
+        client: "alice".to_string(),
+    };
+    let err = client.get_result(request.clone()).await.unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+
+    client.cancel(JobInfo { job_id }).await?;
+    let mut retries = 0;
+    loop {
+        match client.get_result(request.clone()).await.unwrap_err().code() {
+            Code::Cancelled => break,
+            code => assert_eq!(code, Code::FailedPrecondition),
+        }
+        assert!(retries < 500, "the job was never cancelled");
+        retries += 1;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    // submitted again, it is not failed anymore
+    let job_id = client.submit(block).await?.into_inner().job_id;
+    let err = client.get_result(request).await.unwrap_err();
+    assert_eq!(err.code(), Code::FailedPrecondition);
+    client.cancel(JobInfo { job_id }).await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn long_replays_should_not_drop_the_subscriber() -> Result<()> {
+    // more results to replay than the subscriber stream and the HTTP/2 stream window buffer
+    let count = 64;
+    let addr = serve(&Config {
+        channel_size: 2,
+        queue_size: count,
+        ..Default::default()
+    })
+    .await?;
+    let channel = Endpoint::from_shared(format!("http://{}", addr))?
+        .initial_stream_window_size(1024)
+        .connect()
+        .await?;
+    let mut client = PowBuilderClient::new(channel);
+    let block = |data: &str, difficulty| Block {
+        data: data.as_bytes().to_vec(),
+        difficulty,
+        client: "alice".to_string(),
+        ..Default::default()
+    };
+    let blocks: Vec<_> = (0..count)
+        .map(|i| block(&format!("block {}", i), 1))
+        .collect();
+    let mut job_ids = HashSet::new();
+    for block in &blocks {
+        job_ids.insert(client.submit(block.clone()).await?.into_inner().job_id);
+    }
+    for block in &blocks {
+        wait_for_result(&mut client, block).await?;
+    }
+
+    // a job finishes while the replay is not read yet
+    let mut stream = client
+        .subscribe(ClientInfo {
+            name: "alice".to_string(),
+            replay: true,
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    let live = block("live", 12);
+    job_ids.insert(client.submit(live.clone()).await?.into_inner().job_id);
+    wait_for_result(&mut client, &live).await?;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    while !job_ids.is_empty() {
+        match stream.message().await? {
+            Some(notification) => job_ids.remove(&next_hash(notification.kind).job_id),
+            None => anyhow::bail!("the subscriber was dropped"),
+        };
+    }
+    Ok(())
+}
+
+// the result of `block`, once mined
+async fn wait_for_result(
+    client: &mut PowBuilderClient<Channel>,
+    block: &Block,
+) -> Result<BlockHash> {
+    let request = ResultRequest {
+        id: blake3::hash(&block.data).as_bytes().to_vec(),
+        client: block.client.clone(),
+    };
+    let mut retries = 0;
+    loop {
+        match client.get_result(request.clone()).await {
+            Ok(hash) => return Ok(hash.into_inner()),
+            Err(err) => assert_eq!(err.code(), Code::FailedPrecondition),
+        }
+        assert!(retries < 500, "the block was never mined");
+        retries += 1;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+fn next_hash(kind: Option<notification::Kind>) -> BlockHash {
+    match kind {
+        Some(notification::Kind::BlockHash(hash)) => hash,
+        kind => panic!("unexpected notification {:?}", kind),
+    }
+}
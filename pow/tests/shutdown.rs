@@ -0,0 +1,109 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::health::health_check_response::ServingStatus;
+use pow::pb::health::health_client::HealthClient;
+use pow::pb::health::HealthCheckRequest;
+use pow::pb::pow_builder_client::PowBuilderClient;
+use pow::pb::{notification, Block, ClientInfo, Notification, StatusRequest};
+use pow::server::{Config, PowServer};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Code, Status, Streaming};
+
+const GRACE: Duration = Duration::from_millis(500);
+
+fn client(name: &str) -> ClientInfo {
+    ClientInfo {
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+fn block(client: &str) -> Block {
+    Block {
+        data: b"hello world".to_vec(),
+        difficulty: 64,
+        client: client.to_string(),
+        ..Default::default()
+    }
+}
+
+// the result of the job, skipping its progress
+async fn result(stream: &mut Streaming<Notification>) -> Result<(), Status> {
+    loop {
+        match stream.message().await?.and_then(|n| n.kind) {
+            Some(notification::Kind::BlockHash(_)) => return Ok(()),
+            Some(_) => continue,
+            None => return Err(Status::unknown("stream ended without a result")),
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_should_cancel_the_jobs_and_stop_the_server() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+
+    // a single job runs at a time, the others wait in the queue
+    let server = PowServer::new(&Config {
+        concurrent_jobs: 1,
+        queue_size: 8,
+        grace_period: GRACE,
+        ..Default::default()
+    })?;
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(server.serve(TcpListenerStream::new(listener), async {
+        let _ = stopped.await;
+    }));
+
+    let mut client1 = PowBuilderClient::connect(format!("http://{}", addr)).await?;
+    let mut health_client = HealthClient::connect(format!("http://{}", addr)).await?;
+    let check = |service: &str| HealthCheckRequest {
+        service: service.to_string(),
+    };
+    let status = health_client.check(check("")).await?.into_inner().status;
+    assert_eq!(status, ServingStatus::Serving as i32);
+    let err = health_client.check(check("unknown")).await.unwrap_err();
+    assert_eq!(err.code(), Code::NotFound);
+
+    let mut running = client1.subscribe(client("alice")).await?.into_inner();
+    let mut queued = client1.subscribe(client("bob")).await?.into_inner();
+    client1.submit(block("alice")).await?;
+    while client1.status(StatusRequest {}).await?.into_inner().running < 1 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    client1.submit(block("bob")).await?;
+
+    // the server reports it's going away and rejects new jobs
+    stop.send(()).unwrap();
+    let mut retries = 0;
+    while health_client
+        .check(check("abi.PowBuilder"))
+        .await?
+        .into_inner()
+        .status
+        != ServingStatus::NotServing as i32
+    {
+        assert!(retries < 100, "the server never stopped serving");
+        retries += 1;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let err = client1.submit(block("alice")).await.unwrap_err();
+    assert_eq!(err.code(), Code::Unavailable);
+
+    // the running job is cancelled once the grace period is over, then the queued one
+    assert_eq!(
+        result(&mut running).await.unwrap_err().code(),
+        Code::Cancelled
+    );
+    assert_eq!(
+        result(&mut queued).await.unwrap_err().code(),
+        Code::Cancelled
+    );
+    drop(client1);
+    drop(health_client);
+    tokio::time::timeout(GRACE * 4, server).await???;
+    Ok(())
+}
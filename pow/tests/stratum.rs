@@ -0,0 +1,206 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Result;
+use pow::auth::{Auth, Tenant};
+use pow::chain::Chain;
+use pow::pb::Block;
+use pow::stratum::{self, Account, Pool, MAX_LINE_LENGTH};
+use pow::{pow_hash, verify};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+struct Miner {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+}
+
+impl Miner {
+    async fn connect(addr: SocketAddr) -> Result<Self> {
+        let (reader, writer) = TcpStream::connect(addr).await?.into_split();
+        let lines = BufReader::new(reader).lines();
+        Ok(Self {
+            lines,
+            writer,
+            next_id: 0,
+        })
+    }
+
+    async fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let request = json!({ "id": self.next_id, "method": method, "params": params });
+        self.writer
+            .write_all(format!("{}\n", request).as_bytes())
+            .await?;
+        let response = self.next().await?;
+        assert_eq!(response["id"], self.next_id);
+        Ok(response)
+    }
+
+    async fn next(&mut self) -> Result<Value> {
+        let line = self.lines.next_line().await?.unwrap();
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+async fn start_pool() -> Result<(SocketAddr, Arc<Pool>, Arc<Chain>)> {
+    let chain = Arc::new(Chain::default());
+    let pool = Arc::new(Pool::new(chain.clone(), 4, 8));
+    let addr = serve(pool.clone()).await?;
+    Ok((addr, pool, chain))
+}
+
+async fn serve(pool: Arc<Pool>) -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(stratum::serve(listener, pool));
+    Ok(addr)
+}
+
+// the block of a `mining.notify`
+fn job(notify: &Value, extra_nonce: u64) -> (String, Block) {
+    assert_eq!(notify["method"], "mining.notify");
+    let params = &notify["params"];
+    let block = Block {
+        data: hex::decode(params[1].as_str().unwrap()).unwrap(),
+        prev_hash: hex::decode(params[2].as_str().unwrap()).unwrap(),
+        height: params[3].as_u64().unwrap(),
+        timestamp: params[4].as_u64().unwrap(),
+        algorithm: params[5].as_i64().unwrap() as i32,
+        difficulty: params[6].as_u64().unwrap() as u32,
+        extra_nonce,
+        ..Default::default()
+    };
+    (params[0].as_str().unwrap().to_string(), block)
+}
+
+// first nonce whose hash has at least `min` and less than `max` leading zero bits
+fn find(block: &Block, min: u32, max: u32) -> u64 {
+    (0..)
+        .find(|n| verify(block, *n, min) && !verify(block, *n, max))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn shares_should_be_accounted_per_worker() -> Result<()> {
+    let (addr, pool, chain) = start_pool().await?;
+    let mut miner = Miner::connect(addr).await?;
+
+    let subscribed = miner.call("mining.subscribe", json!([])).await?;
+    let extra_nonce = subscribed["result"][1].as_u64().unwrap();
+    let difficulty = miner.next().await?;
+    assert_eq!(difficulty["method"], "mining.set_difficulty");
+    assert_eq!(difficulty["params"], json!([4]));
+    let (job_id, block) = job(&miner.next().await?, extra_nonce);
+    assert_eq!(block.difficulty, 8);
+
+    let share = find(&block, 4, 8);
+    let submit = json!(["alice", job_id, share.to_string()]);
+    let res = miner.call("mining.submit", submit.clone()).await?;
+    assert_eq!(res["error"][0], 24);
+    let res = miner
+        .call("mining.authorize", json!(["alice", "x"]))
+        .await?;
+    assert_eq!(res["result"], true);
+
+    let res = miner.call("mining.submit", submit.clone()).await?;
+    assert_eq!(res["result"], true);
+    let res = miner.call("mining.submit", submit).await?;
+    assert_eq!(res["error"][0], 22);
+    let low = find(&block, 0, 4);
+    let res = miner
+        .call("mining.submit", json!(["alice", job_id, low.to_string()]))
+        .await?;
+    assert_eq!(res["error"][0], 23);
+
+    // a share meeting the block difficulty mines the block, and starts a new job
+    let nonce = find(&block, 8, 257);
+    let res = miner
+        .call("mining.submit", json!(["alice", job_id, nonce.to_string()]))
+        .await?;
+    assert_eq!(res["result"], true);
+    assert_eq!(chain.tip().unwrap().hash, pow_hash(&block, nonce));
+    let notify = miner.next().await?;
+    assert_eq!(notify["params"][7], true);
+    let (next_id, next) = job(&notify, extra_nonce);
+    assert_eq!(next.height, 1);
+    assert_eq!(next.prev_hash, pow_hash(&block, nonce));
+
+    let res = miner
+        .call("mining.submit", json!(["alice", job_id, nonce.to_string()]))
+        .await?;
+    assert_eq!(res["error"][0], 21);
+    assert_ne!(next_id, job_id);
+
+    let account = Account {
+        accepted: 2,
+        rejected: 3,
+        blocks: 1,
+    };
+    assert_eq!(pool.accounts()["alice"], account);
+    Ok(())
+}
+
+#[tokio::test]
+async fn miners_should_search_with_different_extra_nonces() -> Result<()> {
+    let (addr, _, _) = start_pool().await?;
+    let mut extra_nonces = Vec::new();
+    for _ in 0..2 {
+        let mut miner = Miner::connect(addr).await?;
+        let subscribed = miner.call("mining.subscribe", json!([])).await?;
+        extra_nonces.push(subscribed["result"][1].as_u64().unwrap());
+    }
+    assert_ne!(extra_nonces[0], extra_nonces[1]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn workers_should_be_authorized_with_the_token_of_their_tenant() -> Result<()> {
+    let tenant = |name: &str, worker| Tenant {
+        name: name.to_string(),
+        token: format!("{}-token", name),
+        jobs_per_minute: None,
+        max_difficulty: None,
+        worker,
+    };
+    let auth = Arc::new(Auth::new(vec![tenant("alice", true), tenant("bob", false)]));
+    let pool = Pool::new(Arc::new(Chain::default()), 4, 8).with_auth(auth);
+    let addr = serve(Arc::new(pool)).await?;
+    let mut miner = Miner::connect(addr).await?;
+
+    for params in [
+        json!(["alice", "wrong"]),
+        json!(["bob", "alice-token"]),
+        json!(["alice"]),
+        // bob doesn't run workers
+        json!(["bob", "bob-token"]),
+    ] {
+        let res = miner.call("mining.authorize", params).await?;
+        assert_eq!(res["error"][0], 24);
+    }
+    for worker in ["alice", "alice.rig1"] {
+        let res = miner
+            .call("mining.authorize", json!([worker, "alice-token"]))
+            .await?;
+        assert_eq!(res["result"], true);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn overlong_lines_should_close_the_connection() -> Result<()> {
+    let (addr, _, _) = start_pool().await?;
+    let mut miner = Miner::connect(addr).await?;
+    let line = format!("{}\n", "x".repeat(MAX_LINE_LENGTH + 1));
+    miner.writer.write_all(line.as_bytes()).await?;
+    assert!(matches!(miner.lines.next_line().await, Ok(None) | Err(_)));
+
+    // other miners are still served
+    let mut miner = Miner::connect(addr).await?;
+    let subscribed = miner.call("mining.subscribe", json!([])).await?;
+    assert!(subscribed["error"].is_null());
+    Ok(())
+}
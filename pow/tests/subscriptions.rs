@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::{notification, Block, ClientInfo, JobInfo};
+use pow::service::HEARTBEAT_INTERVAL;
+use tonic::Code;
+
+mod common;
+use common::{connect, start_server};
+
+fn client(name: &str) -> ClientInfo {
+    ClientInfo {
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn unsubscribe_should_end_the_stream() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client1 = connect(addr).await?;
+    let mut stream = client1.subscribe(client("alice")).await?.into_inner();
+
+    let status = client1.unsubscribe(client("alice")).await?.into_inner();
+    assert_eq!(status.code, 0);
+    assert!(stream.message().await?.is_none());
+    let status = client1.unsubscribe(client("alice")).await?.into_inner();
+    assert_eq!(status.code, 404);
+    Ok(())
+}
+
+#[tokio::test]
+async fn names_should_be_unique_among_connected_subscribers() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client1 = connect(addr).await?;
+    let mut client2 = connect(addr).await?;
+    let stream = client1.subscribe(client("alice")).await?.into_inner();
+
+    let err = client2.subscribe(client("alice")).await.unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+    let err = client2.subscribe(client("")).await.unwrap_err();
+    assert_eq!(err.code(), Code::InvalidArgument);
+
+    // the name is free again once the first subscriber is gone
+    drop(stream);
+    drop(client1);
+    let mut retries = 0;
+    while let Err(err) = client2.subscribe(client("alice")).await {
+        assert_eq!(err.code(), Code::AlreadyExists);
+        assert!(retries < 100, "alice was never removed");
+        retries += 1;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn idle_subscribers_should_get_heartbeats() -> Result<()> {
+    let addr = start_server(Duration::from_millis(50)).await?;
+    let mut client1 = connect(addr).await?;
+    let mut stream = client1.subscribe(client("alice")).await?.into_inner();
+
+    for _ in 0..2 {
+        let notification = stream.message().await?.unwrap();
+        assert!(matches!(
+            notification.kind,
+            Some(notification::Kind::Heartbeat(_))
+        ));
+    }
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn running_jobs_should_report_their_progress() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client1 = connect(addr).await?;
+    let mut stream = client1.subscribe(client("alice")).await?.into_inner();
+
+    let block = Block {
+        data: b"hello world".to_vec(),
+        difficulty: 64,
+        client: "alice".to_string(),
+        ..Default::default()
+    };
+    let job_id = client1.submit(block).await?.into_inner().job_id;
+    let progress = match stream.message().await?.unwrap().kind {
+        Some(notification::Kind::Progress(progress)) => progress,
+        kind => panic!("unexpected notification {:?}", kind),
+    };
+    assert_eq!(progress.job_id, job_id);
+    assert!(progress.tried > 0);
+    assert!(progress.hash_rate > 0.0);
+    assert!(progress.elapsed_ms >= 1000);
+    assert_eq!(progress.best_hash.len(), 32);
+
+    client1.cancel(JobInfo { job_id }).await?;
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn results_should_only_go_to_the_submitting_client() -> Result<()> {
+    let addr = start_server(Duration::from_millis(50)).await?;
+    let mut client1 = connect(addr).await?;
+    let mut alice = client1.subscribe(client("alice")).await?.into_inner();
+    let mut bob = client1.subscribe(client("bob")).await?.into_inner();
+    let mut carol = client1
+        .subscribe(ClientInfo {
+            broadcast: true,
+            ..client("carol")
+        })
+        .await?
+        .into_inner();
+
+    let block = Block {
+        data: b"hello world".to_vec(),
+        difficulty: 8,
+        client: "alice".to_string(),
+        ..Default::default()
+    };
+    let job_id = client1.submit(block).await?.into_inner().job_id;
+    for stream in [&mut alice, &mut carol] {
+        loop {
+            match stream.message().await?.unwrap().kind {
+                Some(notification::Kind::BlockHash(hash)) => {
+                    assert_eq!(hash.job_id, job_id);
+                    break;
+                }
+                Some(notification::Kind::Heartbeat(_)) => continue,
+                kind => panic!("unexpected notification {:?}", kind),
+            }
+        }
+    }
+
+    // the result was dispatched before alice got it, bob only ever gets heartbeats
+    for _ in 0..3 {
+        let notification = bob.message().await?.unwrap();
+        assert!(matches!(
+            notification.kind,
+            Some(notification::Kind::Heartbeat(_))
+        ));
+    }
+    Ok(())
+}
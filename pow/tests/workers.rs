@@ -0,0 +1,102 @@
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use anyhow::Result;
+use pow::pb::pow_builder_client::PowBuilderClient;
+use pow::pb::{
+    notification, Block, BlockHash, BlockRequest, ClientInfo, Notification, StatusRequest,
+    WorkReport, WorkerInfo,
+};
+use pow::service::HEARTBEAT_INTERVAL;
+use pow::worker::work;
+use pow::{pow_range, verify};
+use tonic::transport::Channel;
+use tonic::Streaming;
+
+mod common;
+use common::{connect, start_server};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn workers_should_mine_the_submitted_blocks() -> Result<()> {
+    let addr = start_server(HEARTBEAT_INTERVAL).await?;
+    let mut client = connect(addr).await?;
+    let mut results = client
+        .subscribe(ClientInfo {
+            name: "client".to_string(),
+            ..Default::default()
+        })
+        .await?
+        .into_inner();
+    let block = |data: &str| Block {
+        data: data.as_bytes().to_vec(),
+        difficulty: 12,
+        client: "client".to_string(),
+        ..Default::default()
+    };
+
+    // a worker searching its ranges by hand, the only one registered
+    let rig = WorkerInfo {
+        name: "rig".to_string(),
+    };
+    let mut units = client.work(rig).await?.into_inner();
+    wait_for_workers(&mut client, 1).await?;
+    let hello = block("hello");
+    let job_id = client.submit(hello.clone()).await?.into_inner().job_id;
+    let unit = units.message().await?.unwrap();
+    assert_eq!((unit.job_id, unit.stop), (job_id, false));
+    let mined = unit.block.unwrap();
+    assert_eq!(mined.data, hello.data);
+    let nonces = unit.nonce_start..unit.nonce_end;
+    let found = pow_range(mined, nonces, &AtomicBool::new(false)).expect("no nonce in the range");
+    let report = WorkReport {
+        worker: "rig".to_string(),
+        job_id,
+        nonce_start: unit.nonce_start,
+        found: true,
+        nonce: found.nonce,
+    };
+    assert_eq!(client.report(report).await?.into_inner().code, 0);
+    let hash = next_hash(&mut results).await?;
+    assert_eq!((hash.job_id, hash.nonce), (job_id, found.nonce));
+    check_chain(&mut client, &hello, &hash).await?;
+    drop(units);
+
+    for name in ["w1", "w2", "w3"] {
+        tokio::spawn(work(connect(addr).await?, name.to_string()));
+    }
+    wait_for_workers(&mut client, 3).await?;
+    let world = block("world");
+    client.submit(world.clone()).await?;
+    let hash = next_hash(&mut results).await?;
+    check_chain(&mut client, &world, &hash).await?;
+    Ok(())
+}
+
+async fn wait_for_workers(client: &mut PowBuilderClient<Channel>, workers: u32) -> Result<()> {
+    while client.status(StatusRequest {}).await?.into_inner().workers != workers {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    Ok(())
+}
+
+async fn next_hash(results: &mut Streaming<Notification>) -> Result<BlockHash> {
+    match results.message().await?.unwrap().kind {
+        Some(notification::Kind::BlockHash(hash)) => Ok(hash),
+        kind => panic!("unexpected notification {:?}", kind),
+    }
+}
+
+// the server linked the block to the chain before mining it
+async fn check_chain(
+    client: &mut PowBuilderClient<Channel>,
+    block: &Block,
+    hash: &BlockHash,
+) -> Result<()> {
+    let request = BlockRequest {
+        hash: hash.hash.clone(),
+    };
+    let mined = client.get_block(request).await?.into_inner();
+    assert_eq!(mined.data, block.data);
+    assert!(verify(&mined, hash.nonce, 12));
+    Ok(())
+}
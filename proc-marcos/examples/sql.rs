@@ -1,5 +1,23 @@
 use proc_marcos::sql;
+use sql_query::Query;
 
 fn main() {
-    sql!(select * from table1 where id = 10 and timestamp > 1000 order by timestamp desc limit 10);
+    let query = sql!(select * from table1 where id = 10 and timestamp > 1000 order by timestamp desc limit 10);
+    println!("{}", query);
+    println!("{:#?}", query);
+
+    // parameters are never part of the SQL text, only their positional placeholders
+    let user_id = 42;
+    let name = "Robert'); DROP TABLE users; --".to_string();
+    let query: Query<(i32, &String)> =
+        sql!(select id, name from users where id = {user_id} or name = {&name} limit 1);
+    assert_eq!(
+        query.sql,
+        "SELECT id, name FROM users WHERE id = $1 OR name = $2 LIMIT 1"
+    );
+    let (id, name) = query.params;
+    println!("{} with {} and {}", query, id, name);
+
+    // does not compile: GROUP BY is not supported by sql!
+    // sql!(select id from table1 group by id);
 }
@@ -3,6 +3,7 @@ extern crate core;
 mod builder;
 mod builder_with_attr;
 mod json_schema;
+mod sql;
 
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
@@ -11,8 +12,11 @@ use crate::builder::BuilderContext;
 use crate::json_schema::{get_string_literal, StructsTemplate};
 
 #[proc_macro]
-pub fn sql(_input: TokenStream) -> TokenStream {
-    "fn hello() { println!(\"hello world\"); }".parse().unwrap()
+pub fn sql(input: TokenStream) -> TokenStream {
+    sql::SqlContext::try_from(proc_macro2::TokenStream::from(input))
+        .map(|sql| sql.render())
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
 
 #[proc_macro]
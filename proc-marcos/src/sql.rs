@@ -0,0 +1,531 @@
+use std::ops::ControlFlow;
+
+use proc_macro2::{Delimiter, Spacing, Span, TokenStream, TokenTree};
+use quote::quote;
+use sqlparser::ast::{
+    visit_expressions, Expr, OrderByExpr, SelectItem, SetExpr, Statement, TableFactor, Value,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::{Parser, ParserError};
+use sqlparser::tokenizer::Tokenizer;
+use syn::{Error, Expr as RustExpr, Result};
+
+// the characters of `--`, `/*` and `*/`
+const COMMENT_CHARS: [char; 3] = ['-', '/', '*'];
+
+/// The SQL text of the macro input, with the span of every token so that errors point at the
+/// part of the input they are about. Every `{expr}` is a parameter, replaced by the positional
+/// placeholder `$1`, `$2`... so that its value never ends up in the SQL text.
+#[derive(Default)]
+struct Source {
+    sql: String,
+    params: Vec<RustExpr>,
+    line: u64,
+    column: u64,
+    // line and column (as counted by sqlparser, from 1) of the first character of a token, its
+    // text and its span
+    tokens: Vec<(u64, u64, String, Span)>,
+}
+
+impl Source {
+    fn push(&mut self, text: &str, span: Span, spacing: Spacing) {
+        if text.is_empty() {
+            return;
+        }
+        // `c--1` is lexed into joint puncts, but `--` and `/*` start SQL comments which would hide
+        // the rest of the input
+        if self.sql.ends_with(COMMENT_CHARS) && text.starts_with(COMMENT_CHARS) {
+            self.sql.push(' ');
+            self.column += 1;
+        }
+        self.tokens
+            .push((self.line, self.column, text.to_string(), span));
+        for c in text.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.sql.push_str(text);
+        // `>=` is lexed into two puncts, the first one joint
+        if spacing == Spacing::Alone {
+            self.sql.push(' ');
+            self.column += 1;
+        }
+    }
+
+    fn extend(&mut self, input: TokenStream) -> Result<()> {
+        for tt in input {
+            match tt {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                    let param =
+                        syn::parse2(group.stream()).map_err(|e| Error::new(group.span(), e))?;
+                    self.params.push(param);
+                    let placeholder = format!("${}", self.params.len());
+                    self.push(&placeholder, group.span(), Spacing::Alone);
+                }
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Bracket => ("[", "]"),
+                        _ => ("", ""),
+                    };
+                    self.push(open, group.span_open(), Spacing::Alone);
+                    self.extend(group.stream())?;
+                    self.push(close, group.span_close(), Spacing::Alone);
+                }
+                TokenTree::Punct(punct) if matches!(punct.as_char(), '$' | '?') => {
+                    return Err(Error::new(
+                        punct.span(),
+                        "sql! parameters are passed as {expr}",
+                    ));
+                }
+                TokenTree::Punct(punct) => {
+                    self.push(&punct.as_char().to_string(), punct.span(), punct.spacing())
+                }
+                TokenTree::Ident(ident) => {
+                    let text = ident.to_string();
+                    self.push(text.trim_start_matches("r#"), ident.span(), Spacing::Alone)
+                }
+                TokenTree::Literal(lit) => {
+                    // rust has no single quoted strings, so "foo" is the SQL string 'foo'
+                    let text = match litrs::Literal::from(&lit) {
+                        litrs::Literal::String(s) => quote_string(s.value()),
+                        litrs::Literal::Char(c) => quote_string(&c.value().to_string()),
+                        _ => lit.to_string(),
+                    };
+                    self.push(&text, lit.span(), Spacing::Alone)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Span of the token at `line` and `column` of the SQL text, of the last token at the end of
+    /// the input.
+    fn span(&self, line: u64, column: u64) -> Span {
+        self.tokens
+            .iter()
+            .rev()
+            .find(|(l, c, ..)| line > 0 && (*l, *c) <= (line, column))
+            .or_else(|| self.tokens.last())
+            .map(|(.., span)| *span)
+            .unwrap_or_else(Span::call_site)
+    }
+
+    /// Span of the first token that is one of `words`, whatever its case.
+    fn find(&self, words: &[&str]) -> Span {
+        self.tokens
+            .iter()
+            .find(|(_, _, text, _)| words.iter().any(|w| text.eq_ignore_ascii_case(w)))
+            .or_else(|| self.tokens.first())
+            .map(|(.., span)| *span)
+            .unwrap_or_else(Span::call_site)
+    }
+
+    fn unsupported(&self, words: &[&str], what: &str) -> Error {
+        Error::new(
+            self.find(words),
+            format!("{} is not supported by sql!", what),
+        )
+    }
+}
+
+fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+// every parameter is bound to a single placeholder of `statement`, `$1` to `$count`, or the first
+// placeholder which isn't
+fn check_placeholders(statement: &Statement, count: usize) -> std::result::Result<(), String> {
+    let mut found = Vec::new();
+    let _ = visit_expressions(statement, |expr| {
+        if let Expr::Value(Value::Placeholder(placeholder)) = expr {
+            found.push(placeholder.clone());
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    let expected: Vec<_> = (1..=count).map(|i| format!("${}", i)).collect();
+    let occurrences = |placeholder| found.iter().filter(|p| *p == placeholder).count();
+    if let Some(placeholder) = expected.iter().find(|p| occurrences(*p) != 1) {
+        return Err(placeholder.clone());
+    }
+    match found.iter().find(|p| !expected.contains(p)) {
+        Some(placeholder) => Err(placeholder.clone()),
+        None => Ok(()),
+    }
+}
+
+/// A `SELECT` from a single table, with optional `WHERE`, `ORDER BY` and `LIMIT` clauses.
+pub struct SqlContext {
+    /// the statement as formatted by sqlparser
+    sql: String,
+    table: String,
+    /// column names, or `*`
+    columns: Vec<String>,
+    filter: Option<String>,
+    /// column names, and whether they are sorted in ascending order
+    order_by: Vec<(String, bool)>,
+    limit: Option<u64>,
+    /// expressions of the `$1`, `$2`... placeholders of `sql`
+    params: Vec<RustExpr>,
+}
+
+impl TryFrom<TokenStream> for SqlContext {
+    type Error = Error;
+
+    fn try_from(input: TokenStream) -> Result<Self> {
+        let mut source = Source {
+            line: 1,
+            column: 1,
+            ..Default::default()
+        };
+        source.extend(input)?;
+
+        let dialect = GenericDialect {};
+        let tokens = Tokenizer::new(&dialect, &source.sql)
+            .tokenize_with_location()
+            .map_err(|e| Error::new(source.span(e.line, e.col), e.message))?;
+        let mut parser = Parser::new(&dialect).with_tokens_with_locations(tokens);
+        let mut statements = parser.parse_statements().map_err(|e| {
+            let message = match e {
+                ParserError::TokenizerError(message) | ParserError::ParserError(message) => message,
+                e => e.to_string(),
+            };
+            // the token found instead of the one expected was consumed, or is the next one
+            let mut location = parser.peek_token().location;
+            if let Some((_, found)) = message.rsplit_once("found: ") {
+                parser.prev_token();
+                let prev = parser.peek_token();
+                if prev.token.to_string() == found {
+                    location = prev.location;
+                }
+            }
+            Error::new(source.span(location.line, location.column), message)
+        })?;
+
+        if statements.len() != 1 {
+            return Err(Error::new(
+                source.find(&[";"]),
+                "sql! takes a single statement",
+            ));
+        }
+        let statement = statements.remove(0);
+        let sql = statement.to_string();
+        // sqlparser writes `- -1` as `--1`, which is a comment
+        if Parser::parse_sql(&dialect, &sql).ok() != Some(vec![statement.clone()]) {
+            return Err(Error::new(
+                source.find(&["-"]),
+                "sql! can't write this query without an SQL comment, add parentheses",
+            ));
+        }
+        if let Err(placeholder) = check_placeholders(&statement, source.params.len()) {
+            return Err(Error::new(
+                source.find(&[&placeholder]),
+                format!("sql! parameter {} is not bound exactly once", placeholder),
+            ));
+        }
+        let query = match statement {
+            Statement::Query(query) => *query,
+            _ => {
+                return Err(Error::new(
+                    source.find(&[]),
+                    "sql! only takes SELECT statements",
+                ))
+            }
+        };
+
+        if query.with.is_some() {
+            return Err(source.unsupported(&["with"], "WITH"));
+        }
+        if query.offset.is_some() {
+            return Err(source.unsupported(&["offset"], "OFFSET"));
+        }
+        if query.fetch.is_some() {
+            return Err(source.unsupported(&["fetch"], "FETCH"));
+        }
+        if !query.locks.is_empty() {
+            return Err(source.unsupported(&["for"], "FOR UPDATE/SHARE"));
+        }
+        let select = match *query.body {
+            SetExpr::Select(select) => select,
+            _ => {
+                let words = ["union", "except", "intersect", "values", "("];
+                return Err(source.unsupported(&words, "Anything but a plain SELECT"));
+            }
+        };
+
+        let clauses = [
+            (select.distinct.is_some(), "distinct", "DISTINCT"),
+            (select.top.is_some(), "top", "TOP"),
+            (select.into.is_some(), "into", "SELECT INTO"),
+            (!select.lateral_views.is_empty(), "lateral", "LATERAL VIEW"),
+            (!select.group_by.is_empty(), "group", "GROUP BY"),
+            (!select.cluster_by.is_empty(), "cluster", "CLUSTER BY"),
+            (
+                !select.distribute_by.is_empty(),
+                "distribute",
+                "DISTRIBUTE BY",
+            ),
+            (!select.sort_by.is_empty(), "sort", "SORT BY"),
+            (select.having.is_some(), "having", "HAVING"),
+            (!select.named_window.is_empty(), "window", "WINDOW"),
+            (select.qualify.is_some(), "qualify", "QUALIFY"),
+        ];
+        if let Some((_, word, what)) = clauses.iter().find(|(present, ..)| *present) {
+            return Err(source.unsupported(&[*word], what));
+        }
+
+        let mut from = select.from;
+        if from.len() != 1 {
+            return Err(Error::new(
+                source.find(&["from"]),
+                "sql! selects from exactly one table",
+            ));
+        }
+        let from = from.remove(0);
+        if !from.joins.is_empty() {
+            return Err(source.unsupported(&["join"], "JOIN"));
+        }
+        let table = match from.relation {
+            TableFactor::Table {
+                name,
+                alias: None,
+                args: None,
+                ..
+            } => name.to_string(),
+            TableFactor::Table {
+                alias: Some(alias), ..
+            } => return Err(source.unsupported(&[&alias.name.value], "Table alias")),
+            _ => return Err(source.unsupported(&["from"], "Anything but a table name in FROM")),
+        };
+
+        let columns = select
+            .projection
+            .into_iter()
+            .map(|item| match item {
+                SelectItem::Wildcard(_) => Ok("*".to_string()),
+                SelectItem::UnnamedExpr(Expr::Identifier(ident)) => Ok(ident.value),
+                SelectItem::ExprWithAlias { alias, .. } => {
+                    Err(source.unsupported(&[&alias.value], "Column alias"))
+                }
+                _ => Err(Error::new(
+                    source.find(&["select"]),
+                    "sql! only selects column names or *",
+                )),
+            })
+            .collect::<Result<_>>()?;
+
+        let order_by = query
+            .order_by
+            .into_iter()
+            .map(|item| match item {
+                OrderByExpr {
+                    expr: Expr::Identifier(ident),
+                    asc,
+                    nulls_first: None,
+                } => Ok((ident.value, asc.unwrap_or(true))),
+                OrderByExpr {
+                    nulls_first: Some(_),
+                    ..
+                } => Err(source.unsupported(&["nulls"], "NULLS FIRST/LAST")),
+                _ => Err(Error::new(
+                    source.find(&["order"]),
+                    "sql! only orders by column names",
+                )),
+            })
+            .collect::<Result<_>>()?;
+
+        let limit = match query.limit {
+            None => None,
+            Some(Expr::Value(Value::Number(n, false))) if n.parse::<u64>().is_ok() => {
+                Some(n.parse().unwrap())
+            }
+            Some(_) => {
+                return Err(Error::new(
+                    source.find(&["limit"]),
+                    "LIMIT must be a non-negative integer",
+                ))
+            }
+        };
+
+        Ok(Self {
+            sql,
+            table,
+            columns,
+            filter: select.selection.map(|e| e.to_string()),
+            order_by,
+            limit,
+            params: source.params,
+        })
+    }
+}
+
+impl SqlContext {
+    /// A `sql_query::Query`, the crates using `sql!` depend on `sql-query`.
+    pub fn render(&self) -> TokenStream {
+        let sql = &self.sql;
+        let table = &self.table;
+        let columns = &self.columns;
+        let filter = match &self.filter {
+            Some(filter) => quote!(Some(#filter)),
+            None => quote!(None),
+        };
+        let order_by = self
+            .order_by
+            .iter()
+            .map(|(column, asc)| quote!((#column, #asc)));
+        let limit = match self.limit {
+            Some(limit) => quote!(Some(#limit)),
+            None => quote!(None),
+        };
+        let params = &self.params;
+        quote!(
+            ::sql_query::Query {
+                sql: #sql,
+                table: #table,
+                columns: &[#(#columns),*],
+                filter: #filter,
+                order_by: &[#(#order_by),*],
+                limit: #limit,
+                params: (#(#params,)*),
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str) -> Result<SqlContext> {
+        SqlContext::try_from(input.parse::<TokenStream>().unwrap())
+    }
+
+    // line and column of the error, from 1
+    fn location(err: &Error) -> (usize, usize) {
+        let start = err.span().start();
+        (start.line, start.column + 1)
+    }
+
+    #[test]
+    fn queries_should_be_parsed() {
+        let sql = parse(
+            "select id, name from users where id = {user_id} and name = \"O'Neil\" \
+             order by name desc, id limit 10",
+        )
+        .unwrap();
+        assert_eq!(
+            sql.sql,
+            "SELECT id, name FROM users WHERE id = $1 AND name = 'O''Neil' \
+             ORDER BY name DESC, id LIMIT 10"
+        );
+        assert_eq!(sql.table, "users");
+        assert_eq!(sql.columns, ["id", "name"]);
+        assert_eq!(sql.filter.unwrap(), "id = $1 AND name = 'O''Neil'");
+        assert_eq!(
+            sql.order_by,
+            [("name".to_string(), false), ("id".to_string(), true)]
+        );
+        assert_eq!(sql.limit, Some(10));
+        assert_eq!(sql.params.len(), 1);
+
+        let sql = parse("select * from t").unwrap();
+        assert_eq!(
+            (sql.columns, sql.filter, sql.limit),
+            (vec!["*".to_string()], None, None)
+        );
+    }
+
+    #[test]
+    fn comment_sequences_should_not_hide_the_rest_of_the_query() {
+        let sql = parse("select * from t where c--1 > 0 limit 1").unwrap();
+        assert_eq!(sql.sql, "SELECT * FROM t WHERE c - -1 > 0 LIMIT 1");
+        assert_eq!(sql.limit, Some(1));
+
+        // sqlparser writes it `c - --1`
+        let err = parse("select * from t where c---1 > 0").err().unwrap();
+        assert_eq!(location(&err), (1, 24));
+        let sql = parse("select * from t where c-(-(-1)) > 0").unwrap();
+        assert_eq!(sql.filter.unwrap(), "c - (-(-1)) > 0");
+    }
+
+    #[test]
+    fn parameters_should_be_bound_once() {
+        let statement = |sql| {
+            Parser::parse_sql(&GenericDialect {}, sql)
+                .unwrap()
+                .remove(0)
+        };
+        let check = |sql, count| check_placeholders(&statement(sql), count);
+        assert_eq!(check("SELECT * FROM t WHERE a = $1 AND b = $2", 2), Ok(()));
+        assert_eq!(check("SELECT * FROM t", 0), Ok(()));
+        assert_eq!(
+            check("SELECT * FROM t WHERE a = $1", 2),
+            Err("$2".to_string())
+        );
+        assert_eq!(
+            check("SELECT * FROM t WHERE a = $2", 1),
+            Err("$1".to_string())
+        );
+        assert_eq!(
+            check("SELECT * FROM t WHERE a = $1 OR a = $1", 1),
+            Err("$1".to_string())
+        );
+        assert_eq!(
+            check("SELECT * FROM t WHERE a = $1 OR a = $2", 1),
+            Err("$2".to_string())
+        );
+
+        let sql = parse("select * from t where a = {x} and b in ({y}, {&z})").unwrap();
+        assert_eq!(sql.filter.unwrap(), "a = $1 AND b IN ($2, $3)");
+        assert_eq!(sql.params.len(), 3);
+    }
+
+    #[test]
+    fn invalid_sql_should_point_at_the_error() {
+        let err = parse("select * from t where a == 1").err().unwrap();
+        assert!(err.to_string().starts_with("Expected an expression"));
+        assert_eq!(location(&err), (1, 26));
+
+        let err = parse("select * from\nt where").err().unwrap();
+        assert!(err.to_string().starts_with("Expected an expression"));
+        assert_eq!(location(&err), (2, 3));
+
+        let err = parse("select * from t where a = $1").err().unwrap();
+        assert_eq!(err.to_string(), "sql! parameters are passed as {expr}");
+        assert_eq!(location(&err), (1, 27));
+    }
+
+    #[test]
+    fn unsupported_clauses_should_be_rejected() {
+        let err = parse("select id from t group by id").err().unwrap();
+        assert_eq!(err.to_string(), "GROUP BY is not supported by sql!");
+        assert_eq!(location(&err), (1, 18));
+
+        for (input, message) in [
+            (
+                "select * from a join b on a.id = b.id",
+                "JOIN is not supported by sql!",
+            ),
+            (
+                "select * from t as u",
+                "Table alias is not supported by sql!",
+            ),
+            (
+                "select * from t limit -1",
+                "LIMIT must be a non-negative integer",
+            ),
+            ("delete from t", "sql! only takes SELECT statements"),
+            (
+                "select * from t; select * from u",
+                "sql! takes a single statement",
+            ),
+        ] {
+            let err = parse(input).err().unwrap();
+            assert_eq!(err.to_string(), message, "{}", input);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! The queries built by `proc_marcos::sql!`, which expands to a `Query` of this crate.
+
+use std::fmt;
+
+/// A query checked at compile time, displayed as its SQL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Query<P> {
+    pub sql: &'static str,
+    pub table: &'static str,
+    /// column names, or `*`
+    pub columns: &'static [&'static str],
+    pub filter: Option<&'static str>,
+    /// column names, and whether they are sorted in ascending order
+    pub order_by: &'static [(&'static str, bool)],
+    pub limit: Option<u64>,
+    /// values of the `$1`, `$2`... placeholders of `sql`, in order
+    pub params: P,
+}
+
+impl<P> fmt::Display for Query<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.sql)
+    }
+}